@@ -0,0 +1,696 @@
+//! Removal backends for duplicate files.
+//!
+//! Removals are first collected into a [`Plan`], which a [`Runner`] either
+//! reports (dry run) or hands to an [`Executor`]. Every file removed by the
+//! tool goes through [`Executor::execute`], so the choice between trashing,
+//! quarantining and permanent deletion is made in one place and every removal
+//! is recorded in the undo journal.
+//!
+//! Files can change between the scan and the moment they are removed, so the
+//! runner re-checks each operation against the disk right before carrying it
+//! out and skips operations whose files no longer match the scan.
+
+use std::fs;
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::duplicates::DuplicateGroup;
+use crate::format::unix_now;
+use crate::journal::{Journal, JournalAction, JournalEntry};
+use crate::policy::KeepPolicy;
+use crate::prune::{remove_empty_tree, Pruner};
+use crate::quarantine::Quarantine;
+use crate::scanner::{hash_file, FileInfo};
+use crate::trash;
+
+/// MD5 hash of zero bytes, shared by every empty file.
+pub const EMPTY_HASH: &str = "d41d8cd98f00b204e9800998ecf8427e";
+
+/// How duplicate files are removed from disk.
+#[derive(Debug, Clone)]
+pub enum RemovalMode {
+    /// Move files to the freedesktop.org Trash so they can be restored.
+    Trash,
+    /// Move files into a quarantine directory, preserving their layout.
+    Quarantine(Quarantine),
+    /// Delete files permanently.
+    Permanent,
+}
+
+impl RemovalMode {
+    /// Removes a single file according to this mode.
+    pub fn remove(&self, path: &Path) -> io::Result<()> {
+        match self {
+            RemovalMode::Trash => trash::move_to_trash(path).map(|_| ()),
+            RemovalMode::Quarantine(quarantine) => quarantine.quarantine(path).map(|_| ()),
+            RemovalMode::Permanent => fs::remove_file(path),
+        }
+    }
+
+    /// Past-tense verb used when reporting a removed file.
+    pub fn verb(&self) -> &'static str {
+        match self {
+            RemovalMode::Trash => "Trashed",
+            RemovalMode::Quarantine(_) => "Quarantined",
+            RemovalMode::Permanent => "Deleted",
+        }
+    }
+
+    /// How a removal in this mode is recorded in the journal.
+    fn journal_action(&self) -> JournalAction {
+        match self {
+            RemovalMode::Trash => JournalAction::Trash,
+            RemovalMode::Quarantine(_) => JournalAction::Quarantine,
+            RemovalMode::Permanent => JournalAction::Delete,
+        }
+    }
+
+    /// Describes where removed files end up, for confirmation prompts.
+    pub fn destination(&self) -> &'static str {
+        match self {
+            RemovalMode::Trash => "move to the Trash",
+            RemovalMode::Quarantine(_) => "quarantine",
+            RemovalMode::Permanent => "permanently delete",
+        }
+    }
+}
+
+/// A single planned removal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Operation {
+    /// The file to remove.
+    pub victim: PathBuf,
+
+    /// The identical copy that stays on disk, if any.
+    pub keeper: Option<PathBuf>,
+
+    /// File size in bytes.
+    pub size: u64,
+
+    /// Content hash shared by the victim and the keeper.
+    pub hash: String,
+
+    /// Modification time of the victim when it was scanned.
+    pub victim_modified: Option<SystemTime>,
+
+    /// Modification time of the keeper when it was scanned.
+    pub keeper_modified: Option<SystemTime>,
+}
+
+impl Operation {
+    /// Checks that the victim and the keeper still match the scan.
+    ///
+    /// Both files must still be regular files with the scanned size and
+    /// modification time. With `check_hash`, their content is re-hashed too.
+    ///
+    /// # Returns
+    ///
+    /// An error describing the first mismatch found.
+    pub fn verify(&self, check_hash: bool) -> io::Result<()> {
+        if let Some(keeper) = &self.keeper {
+            check_unchanged(keeper, "kept copy", self, self.keeper_modified, check_hash)?;
+        }
+        check_unchanged(&self.victim, "file", self, self.victim_modified, check_hash)
+    }
+}
+
+/// Fails unless `path` still has the size, mtime and hash recorded in `op`.
+fn check_unchanged(
+    path: &Path,
+    role: &str,
+    op: &Operation,
+    modified: Option<SystemTime>,
+    check_hash: bool,
+) -> io::Result<()> {
+    let changed = |what: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} {} {} since the scan", role, path.display(), what),
+        )
+    };
+
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} {} no longer exists", role, path.display()),
+            ));
+        }
+        Err(e) => return Err(e),
+    };
+
+    if !metadata.is_file() {
+        return Err(changed("is no longer a regular file"));
+    }
+    if metadata.len() != op.size {
+        return Err(changed("changed size"));
+    }
+    if modified.is_some() && metadata.modified().ok() != modified {
+        return Err(changed("was modified"));
+    }
+    if check_hash && hash_file(path)? != op.hash {
+        return Err(changed("changed content"));
+    }
+
+    Ok(())
+}
+
+/// An ordered list of removals, built before anything touches the disk.
+#[derive(Debug, Clone, Default)]
+pub struct Plan {
+    /// Operations in the order they will be carried out.
+    pub operations: Vec<Operation>,
+}
+
+impl Plan {
+    /// Creates an empty plan.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Plans the removal of every file but the one `policy` keeps in each group.
+    ///
+    /// Protected files are never planned for removal, even when a group
+    /// contains more than one of them.
+    pub fn from_policy(groups: &[DuplicateGroup], policy: &KeepPolicy) -> Self {
+        let mut plan = Self::new();
+        for group in groups {
+            let keep = policy.choose(&group.paths);
+            let indices: Vec<usize> = (0..group.paths.len())
+                .filter(|&i| Some(i) != keep && !policy.is_protected(&group.paths[i]))
+                .collect();
+            plan.add_group(group, &indices);
+        }
+        plan
+    }
+
+    /// Plans the removal of the files at `indices` within a duplicate group.
+    ///
+    /// The first file not selected for removal is recorded as the keeper.
+    pub fn add_group(&mut self, group: &DuplicateGroup, indices: &[usize]) {
+        let keeper = (0..group.paths.len())
+            .find(|i| !indices.contains(i))
+            .map(|i| group.paths[i].clone());
+        let keeper_modified = keeper.as_ref().and_then(|k| group.modified.get(k)).copied();
+
+        for &idx in indices {
+            if let Some(path) = group.paths.get(idx) {
+                self.operations.push(Operation {
+                    victim: path.clone(),
+                    keeper: keeper.clone(),
+                    size: group.size,
+                    hash: group.hash.clone(),
+                    victim_modified: group.modified.get(path).copied(),
+                    keeper_modified,
+                });
+            }
+        }
+    }
+
+    /// Plans the removal of zero-byte files, none of which has a keeper.
+    pub fn add_empty_files(&mut self, files: &[FileInfo]) {
+        for file in files {
+            self.operations.push(Operation {
+                victim: file.path.clone(),
+                keeper: None,
+                size: 0,
+                hash: EMPTY_HASH.to_string(),
+                victim_modified: file.modified,
+                keeper_modified: None,
+            });
+        }
+    }
+
+    /// Total bytes freed if every operation succeeds.
+    pub fn total_bytes(&self) -> u64 {
+        self.operations.iter().map(|op| op.size).sum()
+    }
+}
+
+/// Removes duplicate files and journals each removal.
+#[derive(Debug, Clone)]
+pub struct Executor {
+    /// How removed files are disposed of.
+    pub mode: RemovalMode,
+
+    /// Journal that every removal is appended to.
+    pub journal: Journal,
+}
+
+impl Executor {
+    /// Records one operation in the journal and carries it out.
+    ///
+    /// Permissions and modification time are captured before removal so that
+    /// `undo` can recreate the file exactly. The entry is synced to disk
+    /// before the file is touched, so a crash cannot leave a removal that
+    /// was never journaled; if it cannot be written, the file is left alone.
+    pub fn execute(&self, op: &Operation) -> io::Result<()> {
+        let metadata = fs::symlink_metadata(&op.victim)?;
+
+        let entry = JournalEntry {
+            timestamp: unix_now(),
+            action: self.mode.journal_action(),
+            removed: std::path::absolute(&op.victim)?,
+            kept: op.keeper.as_deref().map(std::path::absolute).transpose()?,
+            size: op.size,
+            hash: op.hash.clone(),
+            mode: metadata.permissions().mode() & 0o7777,
+            modified: metadata.modified()?,
+        };
+
+        self.journal.append(&entry).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!(
+                    "cannot record removal in journal {}: {}",
+                    self.journal.path.display(),
+                    e
+                ),
+            )
+        })?;
+
+        if let Err(e) = self.mode.remove(&op.victim) {
+            if let Err(retract) = self.journal.retract(&entry) {
+                eprintln!(
+                    "  Warning: could not drop {} from journal {}: {}",
+                    op.victim.display(),
+                    self.journal.path.display(),
+                    retract
+                );
+            }
+            return Err(e);
+        }
+
+        Ok(())
+    }
+}
+
+/// Decides whether a [`Plan`] is carried out or only reported.
+///
+/// A dry run holds no [`Executor`], so there is no removal backend it could
+/// reach: dry-run code paths cannot delete, trash or move anything.
+#[derive(Debug, Clone)]
+pub struct Runner {
+    /// Backend that removes files, or `None` for a dry run.
+    executor: Option<Executor>,
+
+    /// Whether files are re-hashed before each operation.
+    check_hash: bool,
+
+    /// Removes directories left empty once the plan has run, if set.
+    pruner: Option<Pruner>,
+}
+
+/// What a [`Runner`] did with a plan.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RunReport {
+    /// Files removed, or that would be removed in a dry run.
+    pub removed: Vec<PathBuf>,
+
+    /// Bytes freed, or that would be freed in a dry run.
+    pub freed: u64,
+
+    /// Empty directories removed afterwards, deepest first.
+    pub pruned_dirs: Vec<PathBuf>,
+}
+
+impl Runner {
+    /// Creates a runner that prints each planned operation without touching
+    /// the disk.
+    pub fn dry_run() -> Self {
+        Self {
+            executor: None,
+            check_hash: false,
+            pruner: None,
+        }
+    }
+
+    /// Creates a runner that carries out each planned operation.
+    pub fn new(executor: Executor) -> Self {
+        Self {
+            executor: Some(executor),
+            check_hash: false,
+            pruner: None,
+        }
+    }
+
+    /// Sets whether files are re-hashed, not only re-checked for size and
+    /// modification time, before each operation.
+    pub fn with_hash_check(mut self, check_hash: bool) -> Self {
+        self.check_hash = check_hash;
+        self
+    }
+
+    /// Removes directories left empty after each plan using `pruner`.
+    pub fn with_pruner(mut self, pruner: Pruner) -> Self {
+        self.pruner = Some(pruner);
+        self
+    }
+
+    /// Returns `true` if this runner only reports operations.
+    pub fn is_dry_run(&self) -> bool {
+        self.executor.is_none()
+    }
+
+    /// Describes what the runner will do with planned files, for prompts.
+    pub fn destination(&self) -> &'static str {
+        match &self.executor {
+            None => "simulate removing",
+            Some(executor) => executor.mode.destination(),
+        }
+    }
+
+    /// Past-tense verb used when reporting a processed file.
+    pub fn verb(&self) -> &'static str {
+        match &self.executor {
+            None => "Would remove",
+            Some(executor) => executor.mode.verb(),
+        }
+    }
+
+    /// Runs every operation in the plan, reporting each one.
+    ///
+    /// Each operation is verified against the disk first and skipped if
+    /// either file changed since the scan. Failures are reported and skipped
+    /// so that one bad file does not stop the rest of the plan. Afterwards
+    /// the pruner, if any, removes directories the removals left empty.
+    ///
+    /// # Returns
+    ///
+    /// What was removed, or would be removed in a dry run.
+    pub fn run(&self, plan: &Plan) -> RunReport {
+        let mut report = RunReport::default();
+
+        for op in &plan.operations {
+            if let Err(e) = op.verify(self.check_hash) {
+                eprintln!("  Skipped {}: {}", op.victim.display(), e);
+                continue;
+            }
+
+            let result = match &self.executor {
+                None => Ok(()),
+                Some(executor) => executor.execute(op),
+            };
+
+            match result {
+                Ok(()) => {
+                    match (self.is_dry_run(), &op.keeper) {
+                        (true, Some(keeper)) => println!(
+                            "  {}: {} (keeping {})",
+                            self.verb(),
+                            op.victim.display(),
+                            keeper.display()
+                        ),
+                        _ => println!("  {}: {}", self.verb(), op.victim.display()),
+                    }
+                    report.removed.push(op.victim.clone());
+                    report.freed += op.size;
+                }
+                Err(e) => eprintln!("  Error removing {}: {}", op.victim.display(), e),
+            }
+        }
+
+        if let Some(pruner) = &self.pruner {
+            report.pruned_dirs = pruner.prune(&report.removed, self.is_dry_run());
+            let verb = if self.is_dry_run() {
+                "Would remove"
+            } else {
+                "Removed"
+            };
+            for dir in &report.pruned_dirs {
+                println!("  {} empty directory: {}", verb, dir.display());
+            }
+        }
+
+        report
+    }
+
+    /// Removes empty directory trees, reporting each one.
+    ///
+    /// Directories are removed outright rather than trashed, since they hold
+    /// nothing to recover. A tree that gained contents since the scan is
+    /// reported and left in place.
+    ///
+    /// # Returns
+    ///
+    /// The directories removed, or that would be removed in a dry run.
+    pub fn remove_empty_dirs(&self, dirs: &[PathBuf]) -> Vec<PathBuf> {
+        let mut removed = Vec::new();
+
+        for dir in dirs {
+            let result = if self.is_dry_run() {
+                Ok(())
+            } else {
+                remove_empty_tree(dir)
+            };
+
+            match result {
+                Ok(()) => {
+                    let verb = if self.is_dry_run() {
+                        "Would remove"
+                    } else {
+                        "Removed"
+                    };
+                    println!("  {} empty directory: {}", verb, dir.display());
+                    removed.push(dir.clone());
+                }
+                Err(e) => eprintln!("  Error removing directory {}: {}", dir.display(), e),
+            }
+        }
+
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tempfile::TempDir;
+
+    fn group(paths: &[PathBuf]) -> DuplicateGroup {
+        DuplicateGroup {
+            hash: "abc".to_string(),
+            size: 4,
+            paths: paths.to_vec(),
+            modified: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_plan_add_group_keeps_first_unselected() {
+        let paths = [
+            PathBuf::from("a.txt"),
+            PathBuf::from("b.txt"),
+            PathBuf::from("c.txt"),
+        ];
+        let mut plan = Plan::new();
+        plan.add_group(&group(&paths), &[0, 2]);
+
+        assert_eq!(plan.operations.len(), 2);
+        assert_eq!(plan.operations[0].victim, paths[0]);
+        assert_eq!(plan.operations[0].keeper, Some(paths[1].clone()));
+        assert_eq!(plan.operations[1].victim, paths[2]);
+        assert_eq!(plan.total_bytes(), 8);
+    }
+
+    #[test]
+    fn test_plan_from_policy_keeps_chosen_copy() {
+        let paths = [
+            PathBuf::from("/dl/a.txt"),
+            PathBuf::from("/music/a.txt"),
+            PathBuf::from("/tmp/a.txt"),
+        ];
+        let policy = KeepPolicy::new(vec!["prefer:/music".parse().unwrap()]);
+
+        let plan = Plan::from_policy(&[group(&paths)], &policy);
+
+        assert_eq!(plan.operations.len(), 2);
+        assert!(plan.operations.iter().all(|op| op.victim != paths[1]));
+        assert!(plan
+            .operations
+            .iter()
+            .all(|op| op.keeper.as_ref() == Some(&paths[1])));
+    }
+
+    #[test]
+    fn test_plan_from_policy_skips_all_protected() {
+        let dir = TempDir::new().unwrap();
+        let paths = [
+            dir.path().join("keep/a.txt"),
+            dir.path().join("keep/b.txt"),
+            dir.path().join("other/c.txt"),
+        ];
+        let policy = KeepPolicy::default().with_protected(&[dir.path().join("keep")]);
+
+        let plan = Plan::from_policy(&[group(&paths)], &policy);
+
+        assert_eq!(plan.operations.len(), 1);
+        assert_eq!(plan.operations[0].victim, paths[2]);
+    }
+
+    #[test]
+    fn test_plan_add_group_without_keeper() {
+        let paths = [PathBuf::from("a.txt"), PathBuf::from("b.txt")];
+        let mut plan = Plan::new();
+        plan.add_group(&group(&paths), &[0, 1]);
+
+        assert!(plan.operations.iter().all(|op| op.keeper.is_none()));
+    }
+
+    #[test]
+    fn test_empty_file_operations() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("zero.txt");
+        fs::write(&path, b"").unwrap();
+        let mut plan = Plan::new();
+        plan.add_empty_files(&[FileInfo {
+            path: path.clone(),
+            size: 0,
+            modified: fs::metadata(&path).unwrap().modified().ok(),
+        }]);
+
+        assert!(plan.operations[0].verify(true).is_ok());
+        assert_eq!(
+            permanent_runner(&dir).run(&plan).removed,
+            vec![path.clone()]
+        );
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_dry_run_leaves_files_in_place() {
+        let dir = TempDir::new().unwrap();
+        let paths = [dir.path().join("a.txt"), dir.path().join("b.txt")];
+        for path in &paths {
+            fs::write(path, b"same").unwrap();
+        }
+
+        let mut plan = Plan::new();
+        plan.add_group(&group(&paths), &[1]);
+
+        assert_eq!(Runner::dry_run().run(&plan).freed, 4);
+        assert!(paths.iter().all(|p| p.exists()));
+    }
+
+    /// Creates identical files and a scanned group with their mtimes.
+    fn scanned_group(dir: &TempDir, names: &[&str]) -> DuplicateGroup {
+        let paths: Vec<PathBuf> = names.iter().map(|n| dir.path().join(n)).collect();
+        for path in &paths {
+            fs::write(path, b"same").unwrap();
+        }
+        DuplicateGroup {
+            hash: hash_file(&paths[0]).unwrap(),
+            size: 4,
+            modified: paths
+                .iter()
+                .map(|p| (p.clone(), fs::metadata(p).unwrap().modified().unwrap()))
+                .collect(),
+            paths,
+        }
+    }
+
+    fn permanent_runner(dir: &TempDir) -> Runner {
+        Runner::new(Executor {
+            mode: RemovalMode::Permanent,
+            journal: Journal::new(dir.path().join("journal")),
+        })
+    }
+
+    #[test]
+    fn test_unchanged_files_are_removed() {
+        let dir = TempDir::new().unwrap();
+        let group = scanned_group(&dir, &["a.txt", "b.txt"]);
+        let mut plan = Plan::new();
+        plan.add_group(&group, &[1]);
+
+        assert_eq!(
+            permanent_runner(&dir)
+                .with_hash_check(true)
+                .run(&plan)
+                .freed,
+            4
+        );
+        assert!(group.paths[0].exists());
+        assert!(!group.paths[1].exists());
+    }
+
+    #[test]
+    fn test_changed_victim_is_skipped() {
+        let dir = TempDir::new().unwrap();
+        let group = scanned_group(&dir, &["a.txt", "b.txt"]);
+        let mut plan = Plan::new();
+        plan.add_group(&group, &[1]);
+
+        fs::write(&group.paths[1], b"longer").unwrap();
+
+        assert_eq!(permanent_runner(&dir).run(&plan).freed, 0);
+        assert!(group.paths[1].exists());
+    }
+
+    #[test]
+    fn test_missing_keeper_is_skipped() {
+        let dir = TempDir::new().unwrap();
+        let group = scanned_group(&dir, &["a.txt", "b.txt"]);
+        let mut plan = Plan::new();
+        plan.add_group(&group, &[1]);
+
+        fs::remove_file(&group.paths[0]).unwrap();
+
+        let err = plan.operations[0].verify(false).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(permanent_runner(&dir).run(&plan).freed, 0);
+        assert!(group.paths[1].exists());
+    }
+
+    #[test]
+    fn test_unjournaled_removal_is_not_carried_out() {
+        let dir = TempDir::new().unwrap();
+        let group = scanned_group(&dir, &["a.txt", "b.txt"]);
+        let mut plan = Plan::new();
+        plan.add_group(&group, &[1]);
+        // A directory in place of the journal makes appending fail.
+        fs::create_dir(dir.path().join("journal")).unwrap();
+
+        assert_eq!(permanent_runner(&dir).run(&plan).freed, 0);
+        assert!(group.paths[1].exists());
+    }
+
+    #[test]
+    fn test_deleting_every_copy_is_journaled_without_keeper() {
+        let dir = TempDir::new().unwrap();
+        let group = scanned_group(&dir, &["a.txt", "b.txt"]);
+        let mut plan = Plan::new();
+        plan.add_group(&group, &[0, 1]);
+
+        assert_eq!(permanent_runner(&dir).run(&plan).freed, 8);
+        assert!(group.paths.iter().all(|p| !p.exists()));
+        let entries = Journal::new(dir.path().join("journal")).read().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries.iter().all(|e| e.kept.is_none()));
+    }
+
+    #[test]
+    fn test_hash_check_catches_same_size_edit() {
+        let dir = TempDir::new().unwrap();
+        let group = scanned_group(&dir, &["a.txt", "b.txt"]);
+        let mut plan = Plan::new();
+        plan.add_group(&group, &[1]);
+
+        // Same size, mtime restored: only the content check can notice.
+        let keeper = &group.paths[0];
+        fs::write(keeper, b"SAME").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(keeper)
+            .unwrap()
+            .set_modified(group.modified[keeper])
+            .unwrap();
+
+        assert!(plan.operations[0].verify(false).is_ok());
+        assert!(plan.operations[0].verify(true).is_err());
+    }
+}
@@ -0,0 +1,187 @@
+//! Directory-based bulk decisions.
+//!
+//! Often the right call is the same for every group: "whatever has a copy in
+//! `Downloads/` and another one elsewhere, drop the Downloads one". This
+//! module lists the directories where such a decision would free space and
+//! plans removing every copy inside one of them that has a twin outside it.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::actions::Plan;
+use crate::duplicates::DuplicateGroup;
+use crate::policy::KeepPolicy;
+
+/// What clearing duplicates out of one directory would remove.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirChoice {
+    /// The directory.
+    pub dir: PathBuf,
+
+    /// Number of groups with a removable copy inside and a copy outside.
+    pub groups: usize,
+
+    /// Number of files that would be removed.
+    pub files: usize,
+
+    /// Bytes that would be freed.
+    pub bytes: u64,
+}
+
+/// Lists the directories holding duplicates that have a copy elsewhere.
+///
+/// Every directory containing a duplicate is considered, including the
+/// ancestors of its parent. A directory that holds all copies of a group
+/// frees nothing for that group, so directories that contain everything,
+/// such as the scan root, drop out on their own.
+///
+/// # Returns
+///
+/// One entry per directory that would free something, most bytes first.
+pub fn dir_choices(groups: &[DuplicateGroup], policy: &KeepPolicy) -> Vec<DirChoice> {
+    let mut choices: HashMap<&Path, DirChoice> = HashMap::new();
+
+    for group in groups {
+        // Copies below each directory: (all, removable).
+        let mut inside: HashMap<&Path, (usize, usize)> = HashMap::new();
+        for path in &group.paths {
+            let removable = !policy.is_protected(path);
+            for dir in path.ancestors().skip(1) {
+                let count = inside.entry(dir).or_default();
+                count.0 += 1;
+                count.1 += removable as usize;
+            }
+        }
+
+        for (dir, (all, removable)) in inside {
+            if all == group.paths.len() || removable == 0 {
+                continue;
+            }
+            let choice = choices.entry(dir).or_insert_with(|| DirChoice {
+                dir: dir.to_path_buf(),
+                groups: 0,
+                files: 0,
+                bytes: 0,
+            });
+            choice.groups += 1;
+            choice.files += removable;
+            choice.bytes += removable as u64 * group.size;
+        }
+    }
+
+    let mut choices: Vec<DirChoice> = choices.into_values().collect();
+    choices.sort_by(|a, b| {
+        Reverse(a.bytes)
+            .cmp(&Reverse(b.bytes))
+            .then_with(|| a.dir.cmp(&b.dir))
+    });
+    choices
+}
+
+/// Plans removing every copy under `dir` that has a copy outside it.
+///
+/// Protected files are never planned for removal.
+///
+/// # Returns
+///
+/// The plan and the indices of the groups it touches, in group order.
+pub fn plan_for_dir(
+    groups: &[DuplicateGroup],
+    dir: &Path,
+    policy: &KeepPolicy,
+) -> (Plan, Vec<usize>) {
+    let mut plan = Plan::new();
+    let mut affected = Vec::new();
+
+    for (index, group) in groups.iter().enumerate() {
+        if group.paths.iter().all(|p| p.starts_with(dir)) {
+            continue;
+        }
+        let indices: Vec<usize> = (0..group.paths.len())
+            .filter(|&i| group.paths[i].starts_with(dir) && !policy.is_protected(&group.paths[i]))
+            .collect();
+        if !indices.is_empty() {
+            plan.add_group(group, &indices);
+            affected.push(index);
+        }
+    }
+
+    (plan, affected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(size: u64, paths: &[&str]) -> DuplicateGroup {
+        DuplicateGroup {
+            hash: format!("hash{}", size),
+            size,
+            paths: paths.iter().map(PathBuf::from).collect(),
+            modified: HashMap::new(),
+        }
+    }
+
+    fn groups() -> Vec<DuplicateGroup> {
+        vec![
+            group(100, &["/home/Documents/a.pdf", "/home/Downloads/a.pdf"]),
+            group(10, &["/home/Documents/b.txt", "/home/Downloads/old/b.txt"]),
+            // Both copies are in Downloads, so neither is removed for it.
+            group(50, &["/home/Downloads/c.zip", "/home/Downloads/c (1).zip"]),
+        ]
+    }
+
+    #[test]
+    fn test_dir_choices() {
+        let choices = dir_choices(&groups(), &KeepPolicy::default());
+
+        let downloads = choices
+            .iter()
+            .find(|c| c.dir == Path::new("/home/Downloads"))
+            .unwrap();
+        assert_eq!(
+            (downloads.groups, downloads.files, downloads.bytes),
+            (2, 2, 110)
+        );
+        assert!(choices.iter().all(|c| c.dir != Path::new("/home")));
+        assert!(choices.windows(2).all(|w| w[0].bytes >= w[1].bytes));
+    }
+
+    #[test]
+    fn test_plan_for_dir_keeps_copies_outside() {
+        let groups = groups();
+
+        let (plan, affected) = plan_for_dir(
+            &groups,
+            Path::new("/home/Downloads"),
+            &KeepPolicy::default(),
+        );
+
+        assert_eq!(affected, vec![0, 1]);
+        assert_eq!(plan.total_bytes(), 110);
+        for op in &plan.operations {
+            assert!(op.victim.starts_with("/home/Downloads"));
+            assert!(op
+                .keeper
+                .as_ref()
+                .is_some_and(|k| k.starts_with("/home/Documents")));
+        }
+    }
+
+    #[test]
+    fn test_protected_copies_are_skipped() {
+        let policy = KeepPolicy {
+            protected: vec![PathBuf::from("/home/Downloads/old")],
+            ..KeepPolicy::default()
+        };
+
+        let (plan, affected) = plan_for_dir(&groups(), Path::new("/home/Downloads"), &policy);
+
+        assert_eq!(affected, vec![0]);
+        assert_eq!(plan.operations.len(), 1);
+        assert!(dir_choices(&groups(), &policy)
+            .iter()
+            .all(|c| c.dir != Path::new("/home/Downloads/old")));
+    }
+}
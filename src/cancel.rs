@@ -0,0 +1,74 @@
+//! Cooperative cancellation with Ctrl-C.
+//!
+//! While a [`CancelGuard`] is alive, the first Ctrl-C only sets a flag that
+//! long-running loops poll through [`requested`], so they can stop early and
+//! keep the work done so far. A second Ctrl-C exits immediately. Dropping the
+//! guard restores the previous handler, so prompts shown afterwards can be
+//! interrupted as usual.
+
+use std::mem;
+use std::ops::ControlFlow;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set by the signal handler on the first Ctrl-C.
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+/// Printed by the handler on the first Ctrl-C.
+const NOTICE: &[u8] = b"\nInterrupted; stopping the scan (press Ctrl-C again to exit now)\n";
+
+extern "C" fn on_sigint(_: libc::c_int) {
+    if CANCELLED.swap(true, Ordering::SeqCst) {
+        // SAFETY: _exit is async-signal-safe.
+        unsafe { libc::_exit(130) };
+    }
+    // SAFETY: write is async-signal-safe and NOTICE outlives the call.
+    unsafe { libc::write(libc::STDERR_FILENO, NOTICE.as_ptr().cast(), NOTICE.len()) };
+}
+
+/// Returns `true` once Ctrl-C has been pressed under a [`CancelGuard`].
+pub fn requested() -> bool {
+    CANCELLED.load(Ordering::SeqCst)
+}
+
+/// Breaks once Ctrl-C has been pressed, for use in progress callbacks.
+pub fn check() -> ControlFlow<()> {
+    if requested() {
+        ControlFlow::Break(())
+    } else {
+        ControlFlow::Continue(())
+    }
+}
+
+/// Installs the cooperative Ctrl-C handler for as long as it lives.
+pub struct CancelGuard {
+    previous: libc::sigaction,
+}
+
+impl CancelGuard {
+    /// Clears any earlier cancellation and installs the handler.
+    pub fn new() -> Self {
+        CANCELLED.store(false, Ordering::SeqCst);
+
+        // SAFETY: both sigaction structs are fully initialised before use,
+        // and the handler only touches an atomic and async-signal-safe calls.
+        unsafe {
+            let mut action: libc::sigaction = mem::zeroed();
+            action.sa_sigaction = on_sigint as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            // Restart interrupted reads rather than failing them with EINTR.
+            action.sa_flags = libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+
+            let mut previous: libc::sigaction = mem::zeroed();
+            libc::sigaction(libc::SIGINT, &action, &mut previous);
+            Self { previous }
+        }
+    }
+}
+
+impl Drop for CancelGuard {
+    fn drop(&mut self) {
+        // SAFETY: restores the action saved by `new`.
+        unsafe { libc::sigaction(libc::SIGINT, &self.previous, ptr::null_mut()) };
+    }
+}
@@ -0,0 +1,465 @@
+//! Checkpoints for resuming interrupted scans.
+//!
+//! While a scan runs, every entry the walk visits and every hash it computes
+//! is appended to a checkpoint file, which is synced to disk every few
+//! seconds. If the scan is cut short, by Ctrl-C or a reboot, replaying the
+//! file rebuilds the walk state and the hashes computed so far, so `--resume`
+//! continues where the scan stopped instead of starting over. The file is
+//! removed once a scan completes.
+//!
+//! The checkpoint is a tab-separated text file with one record per line:
+//!
+//! - `root\t<dir>`: the scanned directory as given on the command line,
+//!   always the first line; a resumed scan that spells the directory
+//!   differently, such as `./dir` for `dir`, adds another `root` line, which
+//!   applies to the records after it
+//! - `dir\t<path>`: a directory
+//! - `file\t<size>\t<mtime>\t<path>`: a regular file, with `mtime` as
+//!   `seconds.nanoseconds` or `-` if unknown
+//! - `other\t<path>`: any other entry
+//! - `unreadable\t<path>`: a directory that could not be read
+//! - `hash\t<hash>\t<path>`: the content hash of a file
+//!
+//! Paths are percent-encoded. A torn last line, left by a crash in the
+//! middle of a write, is ignored.
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use md5::{Digest, Md5};
+
+use crate::format::{decode_mtime, decode_path, encode_mtime, encode_path};
+use crate::journal::state_dir;
+use crate::scanner::{FileInfo, Progress, Visit, WalkState};
+
+/// Minimum time between syncs of the checkpoint file to disk.
+const SYNC_INTERVAL: Duration = Duration::from_secs(10);
+
+/// State of an interrupted scan, read back from a checkpoint file.
+#[derive(Debug, Default)]
+pub struct Checkpoint {
+    /// What the directory walk had found.
+    pub walk: WalkState,
+
+    /// Content hashes computed so far, by path.
+    pub hashes: HashMap<PathBuf, String>,
+
+    /// Length of the file up to the end of its last complete line.
+    len: u64,
+
+    /// The directory as spelled by the last `root` line.
+    recorded_root: PathBuf,
+}
+
+impl Checkpoint {
+    /// Returns the default checkpoint location for scans of `root`,
+    /// `$XDG_STATE_HOME/find_duplicates/checkpoints/<md5 of the path>`.
+    pub fn default_path(root: &Path) -> Option<PathBuf> {
+        let root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+        let key = format!("{:x}", Md5::digest(root.as_os_str().as_bytes()));
+        Some(state_dir()?.join("checkpoints").join(key))
+    }
+
+    /// Reads the checkpoint at `path`, written for a scan of `root`.
+    ///
+    /// The directory may be spelled differently than when the checkpoint was
+    /// written, as long as it is the same directory; recorded paths are
+    /// rebased onto `root`. Hashes of files whose size or modification time
+    /// changed since they were hashed are dropped, so those files are hashed
+    /// again.
+    ///
+    /// # Returns
+    ///
+    /// The checkpoint, `None` if there is none, or an error if the file is
+    /// malformed or belongs to a scan of another directory.
+    pub fn load(path: &Path, root: &Path) -> io::Result<Option<Self>> {
+        let contents = match fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let malformed = |line: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("malformed checkpoint line: {}", line),
+            )
+        };
+
+        // Drop a torn last line; appending resumes right after the rest.
+        let valid = contents.rfind('\n').map_or(0, |i| i + 1);
+        let mut lines = contents[..valid].lines();
+        let first = lines.next().unwrap_or_default();
+        if !matches!(parse_line(first), Some(Record::Root(_))) {
+            return Err(malformed(first));
+        }
+
+        let canonical = |dir: &Path| fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+        let root_canonical = canonical(root);
+        let mut checkpoint = Checkpoint {
+            len: valid as u64,
+            ..Checkpoint::default()
+        };
+        let mut files: HashMap<PathBuf, FileInfo> = HashMap::new();
+        for line in std::iter::once(first).chain(lines) {
+            let rebase = |path: PathBuf| match path.strip_prefix(&checkpoint.recorded_root) {
+                Ok(relative) if checkpoint.recorded_root != root => root.join(relative),
+                _ => path,
+            };
+            match parse_line(line).ok_or_else(|| malformed(line))? {
+                Record::Root(dir) if dir == root || canonical(&dir) == root_canonical => {
+                    checkpoint.recorded_root = dir;
+                }
+                Record::Root(dir) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!(
+                            "{} is a checkpoint for {}, not {}",
+                            path.display(),
+                            dir.display(),
+                            root.display()
+                        ),
+                    ));
+                }
+                Record::Visit(Visit::File(mut info)) => {
+                    info.path = rebase(info.path);
+                    files.insert(info.path.clone(), info.clone());
+                    checkpoint.walk.record(Visit::File(info));
+                }
+                Record::Visit(Visit::Dir(path)) => checkpoint.walk.record(Visit::Dir(rebase(path))),
+                Record::Visit(Visit::Other(path)) => {
+                    checkpoint.walk.record(Visit::Other(rebase(path)))
+                }
+                Record::Visit(Visit::Unreadable(path)) => {
+                    checkpoint.walk.record(Visit::Unreadable(rebase(path)))
+                }
+                Record::Hash(path, hash) => {
+                    checkpoint.hashes.insert(rebase(path), hash);
+                }
+            }
+        }
+
+        checkpoint.hashes.retain(|path, _| {
+            let (Some(info), Ok(metadata)) = (files.get(path), fs::metadata(path)) else {
+                return false;
+            };
+            metadata.len() == info.size && metadata.modified().ok() == info.modified
+        });
+
+        Ok(Some(checkpoint))
+    }
+}
+
+/// Appends the progress of a running scan to a checkpoint file.
+#[derive(Debug)]
+pub struct CheckpointWriter {
+    /// Location of the checkpoint file.
+    path: PathBuf,
+
+    file: BufWriter<File>,
+
+    /// When the file was last synced to disk.
+    last_sync: Instant,
+}
+
+impl CheckpointWriter {
+    /// Starts a new checkpoint for a scan of `root`, replacing any old one.
+    pub fn create(path: &Path, root: &Path) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut writer = Self::new(path, File::create(path)?);
+        writeln!(writer.file, "root\t{}", encode_path(root))?;
+        Ok(writer)
+    }
+
+    /// Continues the checkpoint at `path` that `checkpoint` was loaded from,
+    /// for a scan of `root`.
+    pub fn append(path: &Path, checkpoint: &Checkpoint, root: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().write(true).open(path)?;
+        // Cut off a torn last line so that new records start on their own.
+        file.set_len(checkpoint.len)?;
+        let mut writer = Self::new(path, file);
+        writer.file.seek(SeekFrom::End(0))?;
+        if checkpoint.recorded_root != root {
+            writeln!(writer.file, "root\t{}", encode_path(root))?;
+        }
+        Ok(writer)
+    }
+
+    fn new(path: &Path, file: File) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            file: BufWriter::new(file),
+            last_sync: Instant::now(),
+        }
+    }
+
+    /// Location of the checkpoint file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Records a progress event, syncing to disk every few seconds.
+    ///
+    /// Events that are not needed to resume a scan are ignored.
+    pub fn record(&mut self, progress: Progress) -> io::Result<()> {
+        match progress {
+            Progress::Visited(visit) => writeln!(self.file, "{}", format_visit(visit))?,
+            Progress::Hashed { path, hash } => {
+                writeln!(self.file, "hash\t{}\t{}", hash, encode_path(path))?
+            }
+            _ => return Ok(()),
+        }
+
+        if self.last_sync.elapsed() >= SYNC_INTERVAL {
+            self.sync()?;
+        }
+        Ok(())
+    }
+
+    /// Writes all buffered records and syncs them to disk.
+    pub fn sync(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        self.file.get_ref().sync_data()?;
+        self.last_sync = Instant::now();
+        Ok(())
+    }
+
+    /// Removes the checkpoint once its scan has completed.
+    pub fn remove(self) -> io::Result<()> {
+        fs::remove_file(&self.path)
+    }
+}
+
+/// A parsed checkpoint line.
+enum Record {
+    Root(PathBuf),
+    Visit(Visit),
+    Hash(PathBuf, String),
+}
+
+fn format_visit(visit: &Visit) -> String {
+    match visit {
+        Visit::Dir(path) => format!("dir\t{}", encode_path(path)),
+        Visit::File(info) => format!(
+            "file\t{}\t{}\t{}",
+            info.size,
+            encode_mtime(info.modified),
+            encode_path(&info.path)
+        ),
+        Visit::Other(path) => format!("other\t{}", encode_path(path)),
+        Visit::Unreadable(path) => format!("unreadable\t{}", encode_path(path)),
+    }
+}
+
+fn parse_line(line: &str) -> Option<Record> {
+    let mut fields = line.split('\t');
+    let record = match fields.next()? {
+        "root" => Record::Root(decode_path(fields.next()?)?),
+        "dir" => Record::Visit(Visit::Dir(decode_path(fields.next()?)?)),
+        "file" => {
+            let size = fields.next()?.parse().ok()?;
+            let modified = decode_mtime(fields.next()?)?;
+            Record::Visit(Visit::File(FileInfo {
+                path: decode_path(fields.next()?)?,
+                size,
+                modified,
+            }))
+        }
+        "other" => Record::Visit(Visit::Other(decode_path(fields.next()?)?)),
+        "unreadable" => Record::Visit(Visit::Unreadable(decode_path(fields.next()?)?)),
+        "hash" => {
+            let hash = fields.next()?.to_string();
+            Record::Hash(decode_path(fields.next()?)?, hash)
+        }
+        _ => return None,
+    };
+
+    fields.next().is_none().then_some(record)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::duplicates::find_duplicates;
+    use crate::scanner::{resume_scan, scan_directory};
+    use crate::test_util::write;
+    use std::ops::ControlFlow;
+    use tempfile::TempDir;
+
+    /// Creates a tree with two identical directories, a unique file and an
+    /// empty directory.
+    fn setup() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        for name in ["a/1", "a/2", "b/1", "b/2", "c/3"] {
+            write(&dir.path().join(name), name.rsplit('/').next().unwrap());
+        }
+        fs::create_dir(dir.path().join("empty")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_resumed_scan_matches_full_scan() {
+        let dir = setup();
+        let root = dir.path();
+        let state = TempDir::new().unwrap();
+        let path = state.path().join("checkpoint");
+        let mut writer = CheckpointWriter::create(&path, root).unwrap();
+
+        // Stop the walk after the third file.
+        let scan = scan_directory(root, &mut |p| {
+            writer.record(p).unwrap();
+            match p {
+                Progress::Discovering { files: 3 } => ControlFlow::Break(()),
+                _ => ControlFlow::Continue(()),
+            }
+        })
+        .unwrap();
+        assert_eq!(scan.files.len(), 2);
+        drop(writer);
+
+        let checkpoint = Checkpoint::load(&path, root).unwrap().unwrap();
+        let mut visited = Vec::new();
+        let resumed = resume_scan(root, checkpoint.walk, &mut |p| {
+            if let Progress::Visited(visit) = p {
+                visited.push(visit.path().to_path_buf());
+            }
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+
+        assert!(!visited.contains(&root.join("a")));
+        assert!(!visited.contains(&root.join("a/1")));
+        let full = scan_directory(root, &mut |_| ControlFlow::Continue(())).unwrap();
+        assert_eq!(resumed.files, full.files);
+        assert_eq!(resumed.empties.dirs, vec![root.join("empty")]);
+    }
+
+    #[test]
+    fn test_known_hashes_are_not_recomputed() {
+        let dir = setup();
+        let root = dir.path();
+        let state = TempDir::new().unwrap();
+        let path = state.path().join("checkpoint");
+        let scan = scan_directory(root, &mut |_| ControlFlow::Continue(())).unwrap();
+
+        let mut writer = CheckpointWriter::create(&path, root).unwrap();
+        for file in &scan.files {
+            writer
+                .record(Progress::Visited(&Visit::File(file.clone())))
+                .unwrap();
+        }
+        writer
+            .record(Progress::Hashed {
+                path: &root.join("a/1"),
+                hash: "recorded",
+            })
+            .unwrap();
+        writer.sync().unwrap();
+
+        let checkpoint = Checkpoint::load(&path, root).unwrap().unwrap();
+        let mut hashed = Vec::new();
+        let groups = find_duplicates(scan.files, &checkpoint.hashes, &mut |p| {
+            if let Progress::Hashed { path, .. } = p {
+                hashed.push(path.to_path_buf());
+            }
+            ControlFlow::Continue(())
+        });
+
+        assert!(!hashed.contains(&root.join("a/1")));
+        assert!(hashed.contains(&root.join("b/1")));
+        // The recorded hash is trusted, so a/1 no longer matches b/1.
+        assert!(groups.iter().all(|g| !g.paths.contains(&root.join("a/1"))));
+    }
+
+    #[test]
+    fn test_changed_files_are_hashed_again() {
+        let dir = setup();
+        let root = dir.path();
+        let state = TempDir::new().unwrap();
+        let path = state.path().join("checkpoint");
+        let scan = scan_directory(root, &mut |_| ControlFlow::Continue(())).unwrap();
+
+        let mut writer = CheckpointWriter::create(&path, root).unwrap();
+        for file in &scan.files {
+            writer
+                .record(Progress::Visited(&Visit::File(file.clone())))
+                .unwrap();
+            writer
+                .record(Progress::Hashed {
+                    path: &file.path,
+                    hash: "recorded",
+                })
+                .unwrap();
+        }
+        drop(writer);
+        fs::write(root.join("a/1"), b"longer").unwrap();
+
+        let checkpoint = Checkpoint::load(&path, root).unwrap().unwrap();
+
+        assert_eq!(checkpoint.hashes.len(), scan.files.len() - 1);
+        assert!(!checkpoint.hashes.contains_key(&root.join("a/1")));
+    }
+
+    #[test]
+    fn test_load_rejects_other_root_and_ignores_torn_line() {
+        let dir = setup();
+        let state = TempDir::new().unwrap();
+        let path = state.path().join("checkpoint");
+        fs::write(&path, "root\t/elsewhere\ndir\t/elsewhere/a\n").unwrap();
+        assert!(Checkpoint::load(&path, dir.path()).is_err());
+
+        let root = encode_path(dir.path());
+        fs::write(&path, format!("root\t{root}\ndir\t{root}/a\nfile\t3\t")).unwrap();
+        let checkpoint = Checkpoint::load(&path, dir.path()).unwrap().unwrap();
+        assert!(checkpoint.hashes.is_empty());
+
+        let mut writer = CheckpointWriter::append(&path, &checkpoint, dir.path()).unwrap();
+        writer
+            .record(Progress::Hashed {
+                path: &dir.path().join("a/1"),
+                hash: "recorded",
+            })
+            .unwrap();
+        drop(writer);
+        let contents = fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            contents,
+            format!("root\t{root}\ndir\t{root}/a\nhash\trecorded\t{root}/a/1\n")
+        );
+
+        assert!(Checkpoint::load(&dir.path().join("missing"), dir.path())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_load_accepts_another_spelling_of_root() {
+        let dir = setup();
+        let state = TempDir::new().unwrap();
+        let path = state.path().join("checkpoint");
+        let root = encode_path(dir.path());
+        fs::write(&path, format!("root\t{root}\ndir\t{root}/a\n")).unwrap();
+        let respelled = dir.path().join("a/..");
+
+        let checkpoint = Checkpoint::load(&path, &respelled).unwrap().unwrap();
+        drop(CheckpointWriter::append(&path, &checkpoint, &respelled).unwrap());
+        let reloaded = Checkpoint::load(&path, &respelled).unwrap().unwrap();
+
+        let mut visited = Vec::new();
+        resume_scan(&respelled, reloaded.walk, &mut |p| {
+            if let Progress::Visited(visit) = p {
+                visited.push(visit.path().to_path_buf());
+            }
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+        assert!(visited.iter().all(|p| p.starts_with(&respelled)));
+        assert!(!visited.contains(&respelled.join("a")));
+        assert!(visited.contains(&respelled.join("b")));
+    }
+}
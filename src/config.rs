@@ -0,0 +1,378 @@
+//! Configuration files and named profiles.
+//!
+//! Settings are read from two TOML files: the user's
+//! `$XDG_CONFIG_HOME/find_duplicates/config.toml` and a `.find_duplicates.toml`
+//! at the top of the scanned directory. Each file holds default settings at
+//! the top level and named profiles in `[profiles.<name>]` tables, which are
+//! only applied when selected with `--profile`:
+//!
+//! ```toml
+//! keep = ["prefer:/srv/archive", "oldest"]
+//! protect = ["/srv/archive/originals"]
+//!
+//! [profiles.photos]
+//! keep = ["match:IMG_\\d+\\.jpg$", "oldest"]
+//! verify-hash = true
+//! ```
+//!
+//! Layers are applied in this order, each overriding the ones before it:
+//! the user's defaults, the user's profile, the directory's defaults, the
+//! directory's profile, and finally the command line. Protected directories
+//! add up across all layers instead. Relative paths are relative to the
+//! directory holding the file they appear in.
+//!
+//! The directory's file travels with the files being scanned, so it may
+//! only hold settings that cannot make a run remove files where the user
+//! did not ask for it; see [`Settings::user_only`].
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer};
+
+use crate::duplicates::{GroupOrder, PathOrder};
+use crate::policy::KeepRule;
+use crate::prune::PruneMode;
+
+/// Name of the per-directory configuration file.
+pub const DIR_FILE: &str = ".find_duplicates.toml";
+
+/// Settings that can be given in a configuration file.
+///
+/// Each one mirrors the command-line option of the same name; unset values
+/// leave the option alone.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Settings {
+    #[serde(skip)]
+    pub keep: Option<Vec<KeepRule>>,
+    /// Keep rules as written, parsed once relative paths are resolved.
+    #[serde(rename = "keep")]
+    keep_rules: Option<Vec<String>>,
+    pub protect: Vec<PathBuf>,
+    #[serde(deserialize_with = "value_enum")]
+    pub sort_groups: Option<GroupOrder>,
+    #[serde(deserialize_with = "value_enum")]
+    pub sort_paths: Option<PathOrder>,
+    pub permanent: Option<bool>,
+    pub quarantine: Option<PathBuf>,
+    pub dry_run: Option<bool>,
+    pub overlaps: Option<usize>,
+    #[serde(deserialize_with = "value_enum")]
+    pub prune_empty_dirs: Option<PruneMode>,
+    pub verify_hash: Option<bool>,
+    pub journal: Option<PathBuf>,
+}
+
+impl Settings {
+    /// Overrides these settings with those set in `other`.
+    ///
+    /// Protected directories are added rather than replaced.
+    fn merge(&mut self, other: Settings) {
+        fn set<T>(value: &mut Option<T>, other: Option<T>) {
+            if other.is_some() {
+                *value = other;
+            }
+        }
+
+        set(&mut self.keep, other.keep);
+        self.protect.extend(other.protect);
+        set(&mut self.sort_groups, other.sort_groups);
+        set(&mut self.sort_paths, other.sort_paths);
+        set(&mut self.permanent, other.permanent);
+        set(&mut self.quarantine, other.quarantine);
+        set(&mut self.dry_run, other.dry_run);
+        set(&mut self.overlaps, other.overlaps);
+        set(&mut self.prune_empty_dirs, other.prune_empty_dirs);
+        set(&mut self.verify_hash, other.verify_hash);
+        set(&mut self.journal, other.journal);
+    }
+
+    /// Returns the first setting that may only be given in the user's file.
+    ///
+    /// These choose how and whether files are removed, and where removals
+    /// are recorded, so a file inside a scanned tree must not set them.
+    fn user_only(&self) -> Option<&'static str> {
+        [
+            ("permanent", self.permanent.is_some()),
+            ("quarantine", self.quarantine.is_some()),
+            ("dry-run", self.dry_run.is_some()),
+            ("journal", self.journal.is_some()),
+        ]
+        .into_iter()
+        .find_map(|(key, set)| set.then_some(key))
+    }
+
+    /// Resolves relative paths against `base` and parses the keep rules.
+    fn relative_to(mut self, base: &Path) -> Result<Self, String> {
+        for dir in &mut self.protect {
+            *dir = base.join(&*dir);
+        }
+        for path in [&mut self.quarantine, &mut self.journal]
+            .into_iter()
+            .flatten()
+        {
+            *path = base.join(&*path);
+        }
+        if let Some(rules) = self.keep_rules.take() {
+            let rules = rules
+                .iter()
+                .map(|rule| match rule.strip_prefix("prefer:") {
+                    Some(dir) => format!("prefer:{}", base.join(dir).display()),
+                    None => rule.clone(),
+                })
+                .map(|rule| rule.parse())
+                .collect::<Result<_, _>>()?;
+            self.keep = Some(rules);
+        }
+        Ok(self)
+    }
+}
+
+/// One configuration file: default settings and named profiles.
+#[derive(Debug, Default)]
+struct ConfigFile {
+    settings: Settings,
+    profiles: BTreeMap<String, Settings>,
+}
+
+impl ConfigFile {
+    /// Reads the configuration file at `path`.
+    ///
+    /// # Returns
+    ///
+    /// The file, `None` if there is none, or an error if it is malformed.
+    fn load(path: &Path) -> io::Result<Option<Self>> {
+        let contents = match fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let invalid = |message: String| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), message),
+            )
+        };
+        let mut table: toml::Table = contents
+            .parse()
+            .map_err(|e: toml::de::Error| invalid(e.message().to_string()))?;
+        let profiles = table
+            .remove("profiles")
+            .unwrap_or_else(|| toml::Value::Table(toml::Table::new()));
+        let settings: Settings = toml::Value::Table(table)
+            .try_into()
+            .map_err(|e: toml::de::Error| invalid(e.message().to_string()))?;
+        let profiles: BTreeMap<String, Settings> = profiles
+            .try_into()
+            .map_err(|e: toml::de::Error| invalid(format!("profiles: {}", e.message())))?;
+
+        let base = path.parent().unwrap_or(Path::new(""));
+        let mut file = ConfigFile {
+            settings: settings.relative_to(base).map_err(&invalid)?,
+            profiles: BTreeMap::new(),
+        };
+        for (name, profile) in profiles {
+            let profile = profile
+                .relative_to(base)
+                .map_err(|e| invalid(format!("profile '{}': {}", name, e)))?;
+            file.profiles.insert(name, profile);
+        }
+        Ok(Some(file))
+    }
+}
+
+/// Returns the user's configuration file,
+/// `$XDG_CONFIG_HOME/find_duplicates/config.toml`.
+pub fn user_path() -> Option<PathBuf> {
+    let config_dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+
+    Some(config_dir.join("find_duplicates").join("config.toml"))
+}
+
+/// Reads and merges the configuration for a scan of `dir`.
+///
+/// # Arguments
+///
+/// * `user` - The user's configuration file, if any.
+/// * `dir` - Directory to scan, which may hold a [`DIR_FILE`], or `None`
+///   for commands that do not scan a directory.
+/// * `profile` - Profile to apply on top of the defaults.
+///
+/// # Returns
+///
+/// The merged settings, or an error if a file is malformed, the directory's
+/// file sets a [user-only](Settings::user_only) setting, or the profile is
+/// defined in neither file.
+pub fn load(
+    user: Option<&Path>,
+    dir: Option<&Path>,
+    profile: Option<&str>,
+) -> io::Result<Settings> {
+    let mut files = Vec::new();
+    if let Some(user) = user {
+        files.extend(ConfigFile::load(user)?);
+    }
+    if let Some(dir) = dir {
+        let path = dir.join(DIR_FILE);
+        if let Some(file) = ConfigFile::load(&path)? {
+            let user_only = file.settings.user_only().or_else(|| {
+                file.profiles
+                    .values()
+                    .find_map(|profile| profile.user_only())
+            });
+            if let Some(key) = user_only {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "{}: '{}' can only be set in the user's configuration file",
+                        path.display(),
+                        key
+                    ),
+                ));
+            }
+            files.push(file);
+        }
+    }
+
+    let mut settings = Settings::default();
+    let mut found = false;
+    for mut file in files {
+        settings.merge(file.settings);
+        if let Some(profile) = profile.and_then(|name| file.profiles.remove(name)) {
+            settings.merge(profile);
+            found = true;
+        }
+    }
+
+    match profile {
+        Some(name) if !found => Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no profile named '{}' in the configuration files", name),
+        )),
+        _ => Ok(settings),
+    }
+}
+
+/// Parses a string as one of the values of a command-line option.
+fn value_enum<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: ValueEnum,
+{
+    let s = String::deserialize(deserializer)?;
+    T::from_str(&s, false).map(Some).map_err(|_| {
+        let expected: Vec<String> = T::value_variants()
+            .iter()
+            .filter_map(|v| v.to_possible_value())
+            .map(|v| v.get_name().to_string())
+            .collect();
+        D::Error::custom(format!(
+            "invalid value '{}', expected one of: {}",
+            s,
+            expected.join(", ")
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::write;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_layers_override_in_order() {
+        let home = TempDir::new().unwrap();
+        let user = home.path().join("config.toml");
+        write(
+            &user,
+            "sort-groups = \"size\"\noverlaps = 3\nprotect = [\"/srv/originals\"]\n\
+             [profiles.daily]\noverlaps = 5\ndry-run = true\n",
+        );
+        let dir = TempDir::new().unwrap();
+        write(
+            &dir.path().join(DIR_FILE),
+            "sort-groups = \"count\"\nprotect = [\"masters\"]\n\
+             [profiles.daily]\nkeep = [\"oldest\"]\n",
+        );
+
+        let settings = load(Some(&user), Some(dir.path()), Some("daily")).unwrap();
+
+        assert_eq!(settings.sort_groups, Some(GroupOrder::Count));
+        assert_eq!(settings.overlaps, Some(5));
+        assert_eq!(settings.dry_run, Some(true));
+        assert!(matches!(settings.keep.as_deref(), Some([KeepRule::Oldest])));
+        assert_eq!(
+            settings.protect,
+            vec![PathBuf::from("/srv/originals"), dir.path().join("masters")]
+        );
+
+        let defaults = load(Some(&user), Some(dir.path()), None).unwrap();
+        assert_eq!(defaults.overlaps, Some(3));
+        assert!(defaults.keep.is_none());
+    }
+
+    #[test]
+    fn test_unknown_profile_is_an_error() {
+        let dir = TempDir::new().unwrap();
+        write(&dir.path().join(DIR_FILE), "[profiles.photos]\n");
+
+        assert!(load(None, Some(dir.path()), Some("photos")).is_ok());
+        assert!(load(None, Some(dir.path()), Some("music")).is_err());
+        assert!(load(None, Some(dir.path()), None).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_settings_are_rejected() {
+        let dir = TempDir::new().unwrap();
+        for contents in [
+            "keep = [\"biggest\"]",
+            "sort-groups = \"random\"",
+            "exclude = [\"*.tmp\"]",
+        ] {
+            write(&dir.path().join(DIR_FILE), contents);
+
+            assert!(load(None, Some(dir.path()), None).is_err(), "{}", contents);
+        }
+    }
+
+    #[test]
+    fn test_directory_file_cannot_set_removal_settings() {
+        let home = TempDir::new().unwrap();
+        let user = home.path().join("config.toml");
+        write(
+            &user,
+            "permanent = true
+journal = \"removals\"\n",
+        );
+        let dir = TempDir::new().unwrap();
+        for contents in [
+            "permanent = true",
+            "dry-run = false",
+            "quarantine = \"/tmp/q\"",
+            "[profiles.daily]\njournal = \"journal\"",
+        ] {
+            write(&dir.path().join(DIR_FILE), contents);
+
+            assert!(
+                load(Some(&user), Some(dir.path()), None).is_err(),
+                "{}",
+                contents
+            );
+        }
+
+        let settings = load(Some(&user), None, None).unwrap();
+        assert_eq!(settings.permanent, Some(true));
+        assert_eq!(settings.journal, Some(home.path().join("removals")));
+    }
+}
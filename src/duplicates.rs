@@ -3,9 +3,16 @@
 //! This module provides data structures for representing groups of duplicate files
 //! and functions for computing statistics about disk space usage.
 
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs;
+use std::ops::ControlFlow;
 use std::path::PathBuf;
+use std::time::SystemTime;
 
-use crate::scanner::{self, FileInfo};
+use clap::ValueEnum;
+
+use crate::scanner::{self, FileInfo, Progress};
 
 /// A group of files with identical content.
 ///
@@ -14,7 +21,6 @@ use crate::scanner::{self, FileInfo};
 #[derive(Debug, Clone)]
 pub struct DuplicateGroup {
     /// MD5 hash shared by all files in this group.
-    #[allow(dead_code)]
     pub hash: String,
 
     /// Size in bytes of each file (all files in group have same size).
@@ -22,6 +28,11 @@ pub struct DuplicateGroup {
 
     /// Paths to all duplicate files.
     pub paths: Vec<PathBuf>,
+
+    /// Modification time of each file when it was scanned.
+    ///
+    /// Used to detect files that changed between the scan and an action.
+    pub modified: HashMap<PathBuf, SystemTime>,
 }
 
 impl DuplicateGroup {
@@ -105,31 +116,117 @@ impl DuplicateStats {
 /// # Arguments
 ///
 /// * `files` - Vector of file information from [`scanner::scan_directory`].
+/// * `known` - Hashes computed before an interrupted scan, reused instead of
+///   reading those files again.
+/// * `progress` - Called with [`Progress::Candidates`] once size grouping is
+///   done and with [`Progress::Hashing`] while candidates are hashed. If it
+///   breaks, only groups among the files hashed so far are returned.
 ///
 /// # Returns
 ///
 /// A vector of [`DuplicateGroup`]s, each containing files with identical content.
-pub fn find_duplicates(files: Vec<FileInfo>) -> Vec<DuplicateGroup> {
+pub fn find_duplicates(
+    files: Vec<FileInfo>,
+    known: &HashMap<PathBuf, String>,
+    progress: &mut dyn FnMut(Progress) -> ControlFlow<()>,
+) -> Vec<DuplicateGroup> {
     // First pass: group by size (fast filter)
     let size_groups = scanner::group_by_size(files);
 
     // Flatten all potential duplicates for hashing
     let potential_duplicates: Vec<FileInfo> = size_groups.into_values().flatten().collect();
+    let flow = progress(Progress::Candidates {
+        files: potential_duplicates.len(),
+        bytes: potential_duplicates.iter().map(|f| f.size).sum(),
+    });
+    if flow.is_break() {
+        return Vec::new();
+    }
 
     // Second pass: group by hash (actual duplicates)
-    let hash_groups = scanner::group_by_hash(potential_duplicates);
+    let hash_groups = scanner::group_by_hash(potential_duplicates, known, progress);
 
     // Convert to DuplicateGroup structs
     hash_groups
         .into_iter()
         .map(|(hash, files)| {
             let size = files.first().map(|f| f.size).unwrap_or(0);
+            let modified = files
+                .iter()
+                .filter_map(|f| Some((f.path.clone(), f.modified?)))
+                .collect();
             let paths = files.into_iter().map(|f| f.path).collect();
-            DuplicateGroup { hash, size, paths }
+            DuplicateGroup {
+                hash,
+                size,
+                paths,
+                modified,
+            }
         })
         .collect()
 }
 
+/// Order in which duplicate groups are listed and numbered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GroupOrder {
+    /// Most recoverable space first
+    Wasted,
+    /// Largest files first
+    Size,
+    /// Alphabetically by the group's first path
+    Path,
+    /// Most copies first
+    Count,
+}
+
+/// Order of the paths within each duplicate group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PathOrder {
+    /// Alphabetically by full path
+    Name,
+    /// Oldest modification time first
+    Mtime,
+    /// Fewest directory levels first
+    Depth,
+}
+
+/// Sorts groups and the paths within them into a stable order.
+///
+/// Paths are sorted first, so that [`GroupOrder::Path`] sees each group's
+/// first path. Ties are always broken by path and then hash, which makes
+/// group numbering identical between runs over the same files.
+pub fn sort_groups(groups: &mut [DuplicateGroup], group_order: GroupOrder, path_order: PathOrder) {
+    for group in groups.iter_mut() {
+        match path_order {
+            PathOrder::Name => group.paths.sort(),
+            PathOrder::Mtime => group.paths.sort_by_cached_key(|p| {
+                let modified = fs::metadata(p).and_then(|m| m.modified()).ok();
+                // Unreadable mtimes sort last.
+                (
+                    modified.is_none(),
+                    modified.unwrap_or(SystemTime::UNIX_EPOCH),
+                    p.clone(),
+                )
+            }),
+            PathOrder::Depth => group
+                .paths
+                .sort_by_cached_key(|p| (p.components().count(), p.clone())),
+        }
+    }
+
+    let first_path = |g: &DuplicateGroup| g.paths.iter().min().cloned();
+    groups.sort_by_cached_key(|g| {
+        let primary = match group_order {
+            GroupOrder::Wasted => Reverse(g.wasted_space()),
+            GroupOrder::Size => Reverse(g.size),
+            // Path order is decided by the first-path tie-breaker alone.
+            GroupOrder::Path => Reverse(0),
+            GroupOrder::Count => Reverse(g.paths.len() as u64),
+        };
+        (primary, first_path(g), g.hash.clone())
+    });
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -144,6 +241,7 @@ mod tests {
                 PathBuf::from("b.txt"),
                 PathBuf::from("c.txt"),
             ],
+            modified: HashMap::new(),
         };
 
         // 3 files, 1000 bytes each, 2 are duplicates
@@ -157,6 +255,7 @@ mod tests {
             hash: "abc".to_string(),
             size: 1000,
             paths: vec![PathBuf::from("a.txt")],
+            modified: HashMap::new(),
         };
 
         assert_eq!(group.wasted_space(), 0);
@@ -170,6 +269,7 @@ mod tests {
                 hash: "abc".to_string(),
                 size: 1000,
                 paths: vec![PathBuf::from("a.txt"), PathBuf::from("b.txt")],
+                modified: HashMap::new(),
             },
             DuplicateGroup {
                 hash: "def".to_string(),
@@ -179,6 +279,7 @@ mod tests {
                     PathBuf::from("d.txt"),
                     PathBuf::from("e.txt"),
                 ],
+                modified: HashMap::new(),
             },
         ];
 
@@ -189,6 +290,57 @@ mod tests {
         assert_eq!(stats.total_wasted_bytes, 2000); // 1000 + 500*2
     }
 
+    fn group(hash: &str, size: u64, paths: &[&str]) -> DuplicateGroup {
+        DuplicateGroup {
+            hash: hash.to_string(),
+            size,
+            paths: paths.iter().map(PathBuf::from).collect(),
+            modified: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_sort_groups_by_wasted_space() {
+        let mut groups = vec![
+            group("a", 100, &["/z/1", "/z/2"]),
+            group("b", 10, &["/y/3", "/y/1", "/y/2", "/y/4"]),
+            group("c", 100, &["/x/2", "/x/1", "/x/3"]),
+        ];
+
+        sort_groups(&mut groups, GroupOrder::Wasted, PathOrder::Name);
+
+        let hashes: Vec<&str> = groups.iter().map(|g| g.hash.as_str()).collect();
+        assert_eq!(hashes, ["c", "a", "b"]);
+        assert_eq!(groups[0].paths[0], PathBuf::from("/x/1"));
+    }
+
+    #[test]
+    fn test_sort_groups_ties_broken_by_path() {
+        let mut groups = vec![
+            group("a", 5, &["/b/1", "/b/2"]),
+            group("b", 5, &["/a/1", "/a/2"]),
+        ];
+
+        sort_groups(&mut groups, GroupOrder::Size, PathOrder::Name);
+        assert_eq!(groups[0].hash, "b");
+
+        sort_groups(&mut groups, GroupOrder::Count, PathOrder::Name);
+        assert_eq!(groups[0].hash, "b");
+    }
+
+    #[test]
+    fn test_sort_groups_by_path_and_depth() {
+        let mut groups = vec![
+            group("a", 1, &["/m/a/b/c", "/n/a"]),
+            group("b", 9, &["/z/1", "/z/2"]),
+        ];
+
+        sort_groups(&mut groups, GroupOrder::Path, PathOrder::Depth);
+
+        assert_eq!(groups[0].hash, "a");
+        assert_eq!(groups[0].paths[0], PathBuf::from("/n/a"));
+    }
+
     #[test]
     fn test_format_bytes() {
         assert_eq!(DuplicateStats::format_bytes(500), "500 bytes");
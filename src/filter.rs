@@ -0,0 +1,172 @@
+//! Filters for narrowing down the list of duplicate groups.
+//!
+//! A [`GroupFilter`] is typed as a line of whitespace-separated terms, all of
+//! which must match a group:
+//!
+//! - `.jpg` or `*.jpg`: some copy has this extension
+//! - `>10MB` or `<1KB`: each copy is larger or smaller than this size
+//! - anything else: some copy's path contains the text
+//!
+//! Text and extensions are matched case-insensitively. Sizes use the same
+//! binary units as the rest of the output (`1KB` is 1024 bytes).
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::duplicates::DuplicateGroup;
+
+/// One condition of a [`GroupFilter`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Term {
+    /// Some path contains this lowercase text.
+    Text(String),
+    /// Some path has this lowercase extension.
+    Extension(String),
+    /// Files are larger than this many bytes.
+    Larger(u64),
+    /// Files are smaller than this many bytes.
+    Smaller(u64),
+}
+
+/// A set of conditions that groups must all meet to be listed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GroupFilter {
+    /// The filter as typed, for display.
+    text: String,
+
+    terms: Vec<Term>,
+}
+
+impl GroupFilter {
+    /// Returns `true` if the filter has no terms and matches every group.
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// Returns `true` if `group` meets every term.
+    pub fn matches(&self, group: &DuplicateGroup) -> bool {
+        self.terms.iter().all(|term| match term {
+            Term::Text(text) => group
+                .paths
+                .iter()
+                .any(|p| p.to_string_lossy().to_lowercase().contains(text.as_str())),
+            Term::Extension(ext) => group.paths.iter().any(|p| {
+                p.extension()
+                    .is_some_and(|e| e.to_string_lossy().to_lowercase() == *ext)
+            }),
+            Term::Larger(bytes) => group.size > *bytes,
+            Term::Smaller(bytes) => group.size < *bytes,
+        })
+    }
+}
+
+impl FromStr for GroupFilter {
+    type Err = String;
+
+    /// Parses a filter such as `photos .jpg >1MB`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let terms = s
+            .split_whitespace()
+            .map(|word| {
+                if let Some(size) = word.strip_prefix('>') {
+                    parse_size(size).map(Term::Larger)
+                } else if let Some(size) = word.strip_prefix('<') {
+                    parse_size(size).map(Term::Smaller)
+                } else if let Some(ext) = word.strip_prefix("*.").or(word.strip_prefix('.')) {
+                    Ok(Term::Extension(ext.to_lowercase()))
+                } else {
+                    Ok(Term::Text(word.to_lowercase()))
+                }
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            text: s.trim().to_string(),
+            terms,
+        })
+    }
+}
+
+impl fmt::Display for GroupFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+/// Parses a size such as `512`, `10KB`, `1.5M` or `2GB`.
+fn parse_size(s: &str) -> Result<u64, String> {
+    let upper = s.to_uppercase();
+    let digits = upper.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let unit: u64 = match &upper[digits.len()..] {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        "T" | "TB" => 1 << 40,
+        _ => {
+            return Err(format!(
+                "unknown size unit in '{}' (expected B, KB, MB, GB or TB)",
+                s
+            ))
+        }
+    };
+    let number: f64 = digits
+        .parse()
+        .map_err(|_| format!("invalid size '{}'", s))?;
+    if number < 0.0 {
+        return Err(format!("invalid size '{}'", s));
+    }
+
+    Ok((number * unit as f64) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    fn group(size: u64, paths: &[&str]) -> DuplicateGroup {
+        DuplicateGroup {
+            hash: "hash".to_string(),
+            size,
+            paths: paths.iter().map(PathBuf::from).collect(),
+            modified: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("10kb"), Ok(10 * 1024));
+        assert_eq!(parse_size("1.5M"), Ok(3 * 512 * 1024));
+        assert!(parse_size("10XB").is_err());
+        assert!(parse_size("MB").is_err());
+    }
+
+    #[test]
+    fn test_all_terms_must_match() {
+        let photo = group(2 << 20, &["/home/Photos/IMG_1.JPG", "/backup/img_1.jpg"]);
+        let song = group(4 << 20, &["/music/song.mp3", "/backup/song.mp3"]);
+
+        let filter: GroupFilter = "photos .jpg >1MB".parse().unwrap();
+        assert!(filter.matches(&photo));
+        assert!(!filter.matches(&song));
+
+        let filter: GroupFilter = "backup <3MB".parse().unwrap();
+        assert!(filter.matches(&photo));
+        assert!(!filter.matches(&song));
+
+        let filter: GroupFilter = "*.MP3".parse().unwrap();
+        assert!(filter.matches(&song));
+    }
+
+    #[test]
+    fn test_empty_filter_matches_everything() {
+        let filter: GroupFilter = "  ".parse().unwrap();
+
+        assert!(filter.is_empty());
+        assert!(filter.matches(&group(1, &["/a", "/b"])));
+        assert!(">1zz".parse::<GroupFilter>().is_err());
+    }
+}
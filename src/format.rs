@@ -0,0 +1,160 @@
+//! Text encodings for files written to disk.
+//!
+//! Paths on Unix may contain arbitrary bytes, so they are percent-encoded
+//! before being written to line-based formats such as `.trashinfo` files.
+
+use std::ffi::OsString;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Percent-encodes a path using RFC 2396 escaping.
+///
+/// Unreserved characters and `/` are written as-is; every other byte is
+/// written as `%XX`. The result is always valid ASCII.
+///
+/// # Examples
+///
+/// ```ignore
+/// assert_eq!(encode_path(Path::new("/a b/c")), "/a%20b/c");
+/// ```
+pub fn encode_path(path: &Path) -> String {
+    let mut encoded = String::new();
+
+    for &byte in path.as_os_str().as_bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.!~*'()/".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+
+    encoded
+}
+
+/// Decodes a path produced by [`encode_path`].
+///
+/// Returns `None` if the string contains a malformed `%` escape.
+pub fn decode_path(encoded: &str) -> Option<PathBuf> {
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = encoded.get(i + 1..i + 3)?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    Some(PathBuf::from(OsString::from_vec(decoded)))
+}
+
+/// Formats a modification time as `seconds.nanoseconds` since the Unix
+/// epoch, or `-` if it is unknown.
+pub fn encode_mtime(time: Option<SystemTime>) -> String {
+    time.and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|d| format!("{}.{:09}", d.as_secs(), d.subsec_nanos()))
+        .unwrap_or_else(|| "-".to_string())
+}
+
+/// Decodes a time produced by [`encode_mtime`].
+///
+/// Returns `None` if the string is malformed, or `Some(None)` for `-`.
+pub fn decode_mtime(encoded: &str) -> Option<Option<SystemTime>> {
+    if encoded == "-" {
+        return Some(None);
+    }
+    let (secs, nanos) = encoded.split_once('.')?;
+    let since_epoch = Duration::new(secs.parse().ok()?, nanos.parse().ok()?);
+    Some(Some(UNIX_EPOCH + since_epoch))
+}
+
+/// Returns the current time in whole seconds since the Unix epoch.
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Formats a point in time as `YYYY-MM-DDThh:mm:ss` in the local time zone.
+pub fn local_timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0) as libc::time_t;
+
+    // SAFETY: `tm` is a plain C struct for which all-zeroes is a valid value,
+    // and `localtime_r` only writes through the pointers it is given.
+    let tm = unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&secs, &mut tm);
+        tm
+    };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn test_encode_path_escapes_reserved_bytes() {
+        assert_eq!(encode_path(Path::new("/music/a b.mp3")), "/music/a%20b.mp3");
+        assert_eq!(encode_path(Path::new("100%")), "100%25");
+    }
+
+    #[test]
+    fn test_encode_path_non_utf8_is_ascii() {
+        let path = PathBuf::from(OsStr::from_bytes(b"/tmp/caf\xe9\n.txt"));
+
+        assert_eq!(encode_path(&path), "/tmp/caf%E9%0A.txt");
+    }
+
+    #[test]
+    fn test_decode_path_roundtrip() {
+        let path = PathBuf::from(OsStr::from_bytes(b"/a b/100%/caf\xe9"));
+
+        assert_eq!(decode_path(&encode_path(&path)), Some(path));
+    }
+
+    #[test]
+    fn test_decode_path_malformed() {
+        assert_eq!(decode_path("/bad%2"), None);
+        assert_eq!(decode_path("/bad%zz"), None);
+    }
+
+    #[test]
+    fn test_mtime_roundtrip() {
+        let time = UNIX_EPOCH + Duration::new(1_700_000_000, 5);
+
+        assert_eq!(encode_mtime(Some(time)), "1700000000.000000005");
+        assert_eq!(decode_mtime(&encode_mtime(Some(time))), Some(Some(time)));
+        assert_eq!(decode_mtime("-"), Some(None));
+        assert_eq!(decode_mtime("12"), None);
+    }
+
+    #[test]
+    fn test_local_timestamp_shape() {
+        let stamp = local_timestamp(SystemTime::now());
+
+        assert_eq!(stamp.len(), 19);
+        assert_eq!(&stamp[4..5], "-");
+        assert_eq!(&stamp[10..11], "T");
+    }
+}
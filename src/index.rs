@@ -0,0 +1,386 @@
+//! A persistent index of file hashes.
+//!
+//! Answering "is this file already in the archive?" should not take a scan
+//! of the archive. Scans run with `--update-index` record every file they
+//! see in an index, keyed by size and then by content hash, and `query`
+//! looks files up in it. Only files that share a size with another file get
+//! hashed during a scan, so many entries start out with a size alone; their
+//! hashes are computed the first time a queried file has the same size, and
+//! kept in the index from then on.
+//!
+//! An entry is only trusted while the indexed file keeps the size and
+//! modification time it was indexed with. Stale entries are dropped when a
+//! query comes across them.
+//!
+//! The index is a tab-separated text file with one line per file:
+//! `<size>\t<hash>\t<mtime>\t<path>`, where `hash` is `-` if not computed
+//! yet and `mtime` is `seconds.nanoseconds` or `-` if unknown. Paths are
+//! absolute and percent-encoded.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::SystemTime;
+
+use crate::format::{decode_mtime, decode_path, encode_mtime, encode_path};
+use crate::journal::state_dir;
+use crate::scanner::{hash_file, FileInfo};
+
+/// What the index knows about one file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    hash: Option<String>,
+    modified: Option<SystemTime>,
+}
+
+/// Indexed files by size, then by path.
+#[derive(Debug)]
+pub struct HashIndex {
+    /// Location of the index file.
+    path: PathBuf,
+
+    by_size: BTreeMap<u64, BTreeMap<PathBuf, Entry>>,
+
+    /// Set when entries changed since the index was read.
+    dirty: bool,
+}
+
+impl HashIndex {
+    /// Returns the default index location,
+    /// `$XDG_STATE_HOME/find_duplicates/index`.
+    pub fn default_path() -> Option<PathBuf> {
+        Some(state_dir()?.join("index"))
+    }
+
+    /// Reads the index at `path`, or starts an empty one if there is none.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut index = Self {
+            path: path.to_path_buf(),
+            by_size: BTreeMap::new(),
+            dirty: false,
+        };
+        let contents = match fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(index),
+            Err(e) => return Err(e),
+        };
+
+        for line in contents.lines() {
+            let (size, file, entry) = parse_line(line).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("malformed index line: {}", line),
+                )
+            })?;
+            index.by_size.entry(size).or_default().insert(file, entry);
+        }
+        Ok(index)
+    }
+
+    /// Number of indexed files.
+    pub fn len(&self) -> usize {
+        self.by_size.values().map(BTreeMap::len).sum()
+    }
+
+    /// Records the files of a scan of `root` and the hashes it computed.
+    ///
+    /// After a complete scan, files previously indexed under `root` that the
+    /// scan did not see are removed. After an interrupted one, entries are
+    /// only added or updated. Hashes of unchanged files are kept even if the
+    /// scan did not compute them. Empty files are not indexed.
+    pub fn update(
+        &mut self,
+        root: &Path,
+        files: &[FileInfo],
+        hashes: &BTreeMap<PathBuf, String>,
+        complete: bool,
+    ) -> io::Result<()> {
+        let mut previous = BTreeMap::new();
+        if complete {
+            let root = std::path::absolute(root)?;
+            for (&size, entries) in &mut self.by_size {
+                let (under, rest) = std::mem::take(entries)
+                    .into_iter()
+                    .partition(|(path, _)| path.starts_with(&root));
+                *entries = rest;
+                previous.extend(under.into_iter().map(|(path, entry)| (path, (size, entry))));
+            }
+        }
+
+        // The size each remaining file is filed under, so that a file seen
+        // again is found without searching every size.
+        let mut sizes: HashMap<PathBuf, u64> = self
+            .by_size
+            .iter()
+            .flat_map(|(&size, entries)| entries.keys().map(move |path| (path.clone(), size)))
+            .collect();
+        for file in files.iter().filter(|f| f.size > 0) {
+            let path = std::path::absolute(&file.path)?;
+            if let Some(size) = sizes.remove(&path) {
+                if let Some(entry) = self.by_size.get_mut(&size).and_then(|e| e.remove(&path)) {
+                    previous.insert(path.clone(), (size, entry));
+                }
+            }
+            let hash = hashes.get(&file.path).cloned().or_else(|| {
+                let (size, entry) = previous.remove(&path)?;
+                (size == file.size && entry.modified == file.modified)
+                    .then_some(entry.hash)
+                    .flatten()
+            });
+            self.by_size.entry(file.size).or_default().insert(
+                path,
+                Entry {
+                    hash,
+                    modified: file.modified,
+                },
+            );
+        }
+        self.by_size.retain(|_, entries| !entries.is_empty());
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Finds the indexed files with the same content as `file`.
+    ///
+    /// Indexed files of the same size are hashed if their hash is not known
+    /// yet. Entries whose files are gone or changed are dropped instead of
+    /// matched, and `file` itself never matches.
+    ///
+    /// # Returns
+    ///
+    /// The matching files, or an error if `file` cannot be read.
+    pub fn lookup(&mut self, file: &Path) -> io::Result<Vec<PathBuf>> {
+        let metadata = fs::metadata(file)?;
+        if metadata.len() == 0 {
+            return Ok(Vec::new());
+        }
+        let Some(entries) = self.by_size.get_mut(&metadata.len()) else {
+            return Ok(Vec::new());
+        };
+
+        let this = fs::canonicalize(file)?;
+        let hash = hash_file(file)?;
+        let mut matches = Vec::new();
+        let mut stale = Vec::new();
+        for (path, entry) in entries.iter_mut() {
+            let current = fs::metadata(path).is_ok_and(|m| {
+                m.is_file() && m.len() == metadata.len() && m.modified().ok() == entry.modified
+            });
+            if !current {
+                stale.push(path.clone());
+                continue;
+            }
+            if fs::canonicalize(path).is_ok_and(|p| p == this) {
+                continue;
+            }
+
+            if entry.hash.is_none() {
+                match hash_file(path) {
+                    Ok(hash) => entry.hash = Some(hash),
+                    Err(_) => {
+                        stale.push(path.clone());
+                        continue;
+                    }
+                }
+                self.dirty = true;
+            }
+            if entry.hash.as_ref() == Some(&hash) {
+                matches.push(path.clone());
+            }
+        }
+
+        for path in &stale {
+            entries.remove(path);
+        }
+        if entries.is_empty() {
+            self.by_size.remove(&metadata.len());
+        }
+        self.dirty |= !stale.is_empty();
+        Ok(matches)
+    }
+
+    /// Writes the index to its file if anything changed.
+    ///
+    /// The file is replaced in one step through a temporary file named after
+    /// this process, so concurrent saves never write to the same temporary
+    /// file; the last one to finish wins.
+    pub fn save(&mut self) -> io::Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+
+        let mut contents = String::new();
+        for (size, entries) in &self.by_size {
+            for (path, entry) in entries {
+                contents.push_str(&format!(
+                    "{}\t{}\t{}\t{}\n",
+                    size,
+                    entry.hash.as_deref().unwrap_or("-"),
+                    encode_mtime(entry.modified),
+                    encode_path(path)
+                ));
+            }
+        }
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(format!(".{}.tmp", process::id()));
+        fs::write(&tmp, contents)?;
+        fs::rename(tmp, &self.path)?;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+/// Outcome of looking files up in the index.
+#[derive(Debug, Default)]
+pub struct QueryResult {
+    /// Copies already in the index, for each file that has any.
+    pub found: BTreeMap<PathBuf, Vec<PathBuf>>,
+    /// Files that could not be read, with the reason.
+    pub unreadable: Vec<(PathBuf, io::Error)>,
+}
+
+/// Looks up every file in `files`.
+pub fn query(index: &mut HashIndex, files: &[PathBuf]) -> QueryResult {
+    let mut result = QueryResult::default();
+    for file in files {
+        match index.lookup(file) {
+            Ok(matches) if matches.is_empty() => {}
+            Ok(matches) => {
+                result.found.insert(file.clone(), matches);
+            }
+            Err(e) => result.unreadable.push((file.clone(), e)),
+        }
+    }
+    result
+}
+
+fn parse_line(line: &str) -> Option<(u64, PathBuf, Entry)> {
+    let mut fields = line.split('\t');
+    let size = fields.next()?.parse().ok()?;
+    let hash = match fields.next()? {
+        "-" => None,
+        hash => Some(hash.to_string()),
+    };
+    let modified = decode_mtime(fields.next()?)?;
+    let path = decode_path(fields.next()?)?;
+
+    fields
+        .next()
+        .is_none()
+        .then_some((size, path, Entry { hash, modified }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::scan_directory;
+    use crate::test_util::write;
+    use std::ops::ControlFlow;
+    use tempfile::TempDir;
+
+    /// Indexes an archive with one hashed pair and one file of unique size.
+    fn setup() -> (TempDir, HashIndex) {
+        let dir = TempDir::new().unwrap();
+        let archive = dir.path().join("archive");
+        write(&archive.join("a"), "same");
+        write(&archive.join("b"), "same");
+        write(&archive.join("unique"), "unique contents");
+        write(&archive.join("empty"), "");
+
+        let scan = scan_directory(&archive, &mut |_| ControlFlow::Continue(())).unwrap();
+        let hashes: BTreeMap<PathBuf, String> = [archive.join("a"), archive.join("b")]
+            .into_iter()
+            .map(|p| {
+                let hash = hash_file(&p).unwrap();
+                (p, hash)
+            })
+            .collect();
+        let mut index = HashIndex::open(&dir.path().join("index")).unwrap();
+        index.update(&archive, &scan.files, &hashes, true).unwrap();
+        (dir, index)
+    }
+
+    #[test]
+    fn test_lookup_hashes_unknown_entries_on_demand() {
+        let (dir, mut index) = setup();
+        write(&dir.path().join("incoming/copy"), "unique contents");
+        write(&dir.path().join("incoming/other"), "unique CONTENTS");
+
+        let matches = index.lookup(&dir.path().join("incoming/copy")).unwrap();
+
+        assert_eq!(matches, vec![dir.path().join("archive/unique")]);
+        assert!(index
+            .lookup(&dir.path().join("incoming/other"))
+            .unwrap()
+            .is_empty());
+        index.save().unwrap();
+        let reopened = HashIndex::open(&dir.path().join("index")).unwrap();
+        assert_eq!(reopened.len(), 3);
+        assert!(reopened.by_size[&15].values().all(|e| e.hash.is_some()));
+    }
+
+    #[test]
+    fn test_stale_entries_are_dropped() {
+        let (dir, mut index) = setup();
+        write(&dir.path().join("incoming/x"), "same");
+        write(&dir.path().join("archive/b"), "diff");
+
+        let matches = index.lookup(&dir.path().join("incoming/x")).unwrap();
+
+        assert_eq!(matches, vec![dir.path().join("archive/a")]);
+        assert_eq!(index.len(), 2);
+        // An indexed file is not its own duplicate.
+        assert!(index
+            .lookup(&dir.path().join("archive/unique"))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_complete_scan_replaces_entries_under_root() {
+        let (dir, mut index) = setup();
+        let archive = dir.path().join("archive");
+        fs::remove_file(archive.join("unique")).unwrap();
+
+        let scan = scan_directory(&archive, &mut |_| ControlFlow::Continue(())).unwrap();
+        index
+            .update(&archive, &scan.files, &BTreeMap::new(), false)
+            .unwrap();
+        assert_eq!(index.len(), 3);
+
+        index
+            .update(&archive, &scan.files, &BTreeMap::new(), true)
+            .unwrap();
+        assert_eq!(index.len(), 2);
+        // Hashes from the first scan survive a scan that did not compute them.
+        assert!(index.by_size[&4].values().all(|e| e.hash.is_some()));
+    }
+
+    #[test]
+    fn test_update_refiles_file_that_changed_size() {
+        let (dir, mut index) = setup();
+        let archive = dir.path().join("archive");
+        write(&archive.join("unique"), "grown unique contents");
+
+        let scan = scan_directory(&archive, &mut |_| ControlFlow::Continue(())).unwrap();
+        index
+            .update(&archive, &scan.files, &BTreeMap::new(), false)
+            .unwrap();
+
+        assert_eq!(index.len(), 3);
+        assert!(!index.by_size.contains_key(&15));
+        assert_eq!(
+            index.by_size[&21].get(&archive.join("unique")),
+            Some(&Entry {
+                hash: None,
+                modified: scan.files.iter().find(|f| f.size == 21).unwrap().modified,
+            })
+        );
+    }
+}
@@ -3,20 +3,43 @@
 //! Provides terminal-based user interaction for reviewing duplicate files,
 //! selecting files to delete, and confirming destructive actions.
 
-use std::fs;
+use std::collections::HashSet;
 use std::io;
+use std::path::Path;
 
-use dialoguer::{Confirm, MultiSelect, Select};
+use dialoguer::{Confirm, Input, MultiSelect, Select};
 
+use crate::actions::{Plan, RunReport, Runner};
+use crate::bulk::{dir_choices, plan_for_dir};
 use crate::duplicates::{DuplicateGroup, DuplicateStats};
+use crate::filter::GroupFilter;
+use crate::index::QueryResult;
+use crate::manifest::Verification;
+use crate::overlap::Overlap;
+use crate::policy::KeepPolicy;
+use crate::preview;
+use crate::quarantine::ManifestEntry;
+use crate::scanner::Empties;
+use crate::trees::DirGroup;
+
+/// Number of affected groups listed before a bulk removal.
+const PREVIEW_GROUPS: usize = 10;
 
 /// Actions available from the main menu.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Action {
     /// Review a specific duplicate group by index.
     ReviewGroup(usize),
-    /// Delete all duplicates, keeping the first file in each group.
+    /// Delete all duplicates, keeping one file per group chosen by the keep policy.
     DeleteAllDuplicates,
+    /// Delete every duplicate inside a chosen directory that has a copy elsewhere.
+    DeleteFromDirectory,
+    /// Review all groups in the full-screen view and apply the decisions.
+    FullScreenReview,
+    /// Remove copies of a duplicate directory by index.
+    RemoveDuplicateDir(usize),
+    /// Delete the empty files and directories found by the scan.
+    DeleteEmpties,
     /// Rescan the directory for duplicates.
     Rescan,
     /// Exit the program.
@@ -61,81 +84,388 @@ pub fn display_summary(groups: &[DuplicateGroup], stats: &DuplicateStats) {
     println!("\n{}", "-".repeat(60));
 }
 
+/// Displays the empty files and directory trees found by the scan.
+///
+/// Empty files all share the same (lack of) content, so they are listed in
+/// their own section rather than as one huge duplicate group.
+pub fn display_empties(empties: &Empties) {
+    if empties.is_empty() {
+        return;
+    }
+
+    println!("\n{}", "=".repeat(60));
+    println!("EMPTY FILES AND DIRECTORIES");
+    println!("{}", "=".repeat(60));
+
+    if !empties.files.is_empty() {
+        println!("\n{} empty file(s):", empties.files.len());
+        for file in &empties.files {
+            println!("  {}", file.path.display());
+        }
+    }
+    if !empties.dirs.is_empty() {
+        println!("\n{} empty directory tree(s):", empties.dirs.len());
+        for dir in &empties.dirs {
+            println!("  {}/", dir.display());
+        }
+    }
+    println!("\n{}", "-".repeat(60));
+}
+
+/// Displays how a tree differs from a checksum manifest.
+pub fn display_verification(result: &Verification) {
+    println!("\n{}", "=".repeat(60));
+    println!("MANIFEST VERIFICATION");
+    println!("{}", "=".repeat(60));
+
+    for (title, paths) in [
+        ("changed content", &result.changed),
+        ("missing", &result.missing),
+        ("new", &result.new),
+    ] {
+        if !paths.is_empty() {
+            println!("\n{} file(s) {}:", paths.len(), title);
+            for path in paths {
+                println!("  {}", path.display());
+            }
+        }
+    }
+    if !result.unreadable.is_empty() {
+        println!("\n{} file(s) could not be read:", result.unreadable.len());
+        for (path, e) in &result.unreadable {
+            println!("  {}: {}", path.display(), e);
+        }
+    }
+
+    println!(
+        "\n{} file(s) match, {} changed, {} missing, {} new",
+        result.matched,
+        result.changed.len(),
+        result.missing.len(),
+        result.new.len()
+    );
+    if result.interrupted {
+        println!("Verification interrupted; files not checked yet are not reported");
+    }
+    println!("{}", "-".repeat(60));
+}
+
+/// Lists the quarantined files a purge would delete.
+pub fn display_purge(entries: &[ManifestEntry]) {
+    println!(
+        "\n{} quarantined file(s) past the grace period:",
+        entries.len()
+    );
+    for entry in entries {
+        println!("  {}", entry.original.display());
+    }
+}
+
+/// Asks whether to permanently delete `count` quarantined files.
+pub fn confirm_purge(count: usize) -> io::Result<bool> {
+    Confirm::new()
+        .with_prompt(format!(
+            "Permanently delete these {} file(s)? This cannot be undone",
+            count
+        ))
+        .default(false)
+        .interact()
+        .map_err(io::Error::other)
+}
+
+/// Displays the queried files that already have copies in the hash index.
+///
+/// Matches go to standard output, one `<file>` line followed by an indented
+/// line per copy, so a hook can read them; the summary goes to standard
+/// error.
+pub fn display_query(result: &QueryResult, queried: usize) {
+    for (file, copies) in &result.found {
+        println!("{}", file.display());
+        for copy in copies {
+            println!("  {}", copy.display());
+        }
+    }
+    for (file, e) in &result.unreadable {
+        eprintln!("Error reading '{}': {}", file.display(), e);
+    }
+    eprintln!(
+        "{} of {} file(s) already indexed",
+        result.found.len(),
+        queried
+    );
+}
+
+/// Displays directory trees with identical contents.
+pub fn display_trees(trees: &[DirGroup]) {
+    if trees.is_empty() {
+        return;
+    }
+
+    println!("\n{}", "=".repeat(60));
+    println!("DUPLICATE DIRECTORIES");
+    println!("{}", "=".repeat(60));
+
+    for (i, tree) in trees.iter().enumerate() {
+        println!(
+            "\nDirectory group {} - {} in {} file(s), {} copies",
+            i + 1,
+            DuplicateStats::format_bytes(tree.size),
+            tree.file_count,
+            tree.dirs.len()
+        );
+        for dir in &tree.dirs {
+            println!("  {}/", dir.display());
+        }
+    }
+    println!("\n{}", "-".repeat(60));
+}
+
+/// Displays the directory pairs that share the most content.
+pub fn display_overlaps(overlaps: &[Overlap]) {
+    if overlaps.is_empty() {
+        return;
+    }
+
+    println!("\n{}", "=".repeat(60));
+    println!("OVERLAPPING DIRECTORIES");
+    println!("{}", "=".repeat(60));
+
+    for (i, overlap) in overlaps.iter().enumerate() {
+        println!(
+            "\nPair {} - {:.1}% similar by bytes, {:.1}% by files; {} shared in {} file(s)",
+            i + 1,
+            overlap.byte_similarity() * 100.0,
+            overlap.file_similarity() * 100.0,
+            DuplicateStats::format_bytes(overlap.shared_bytes),
+            overlap.shared_files
+        );
+        let coverage = overlap.byte_coverage();
+        for (side, dir) in overlap.dirs.iter().enumerate() {
+            println!(
+                "  {}/ ({:.1}% of {} in {} file(s) shared)",
+                dir.display(),
+                coverage[side] * 100.0,
+                DuplicateStats::format_bytes(overlap.bytes[side]),
+                overlap.files[side]
+            );
+        }
+    }
+    println!("\n{}", "-".repeat(60));
+}
+
 /// Displays the main menu and returns the user's selected action.
 ///
 /// # Arguments
 ///
-/// * `group_count` - Number of duplicate groups available to review.
+/// * `groups` - Duplicate groups available to review.
+/// * `decided` - Whether each group was already reviewed in a saved session.
+/// * `tree_count` - Number of duplicate directory groups.
+/// * `empty_count` - Number of empty files and directory trees found.
 ///
 /// # Returns
 ///
 /// The selected [`Action`], or an IO error if the terminal is unavailable.
-pub fn show_main_menu(group_count: usize) -> io::Result<Action> {
-    if group_count == 0 {
+pub fn show_main_menu(
+    groups: &[DuplicateGroup],
+    decided: &[bool],
+    tree_count: usize,
+    empty_count: usize,
+) -> io::Result<Action> {
+    let group_count = groups.len();
+    if group_count == 0 && tree_count == 0 && empty_count == 0 {
         return Ok(Action::Quit);
     }
 
-    let options = vec![
-        format!("Review a specific group (1-{})", group_count),
-        "Delete all duplicates (keep first of each group)".to_string(),
-        "Rescan directory".to_string(),
-        "Quit".to_string(),
-    ];
-
-    let selection = Select::new()
-        .with_prompt("What would you like to do?")
-        .items(&options)
-        .default(0)
-        .interact()
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    let mut options = Vec::new();
+    if group_count > 0 {
+        options.push((
+            format!("Review a specific group (1-{})", group_count),
+            Action::ReviewGroup(0),
+        ));
+        options.push((
+            "Review all groups in full-screen view".to_string(),
+            Action::FullScreenReview,
+        ));
+        options.push((
+            "Delete all duplicates (keep one per group)".to_string(),
+            Action::DeleteAllDuplicates,
+        ));
+        options.push((
+            "Delete duplicates inside a directory (keep copies elsewhere)".to_string(),
+            Action::DeleteFromDirectory,
+        ));
+    }
+    if tree_count > 0 {
+        options.push((
+            format!("Remove a duplicate directory (1-{})", tree_count),
+            Action::RemoveDuplicateDir(0),
+        ));
+    }
+    if empty_count > 0 {
+        options.push((
+            format!("Delete empty files and directories ({})", empty_count),
+            Action::DeleteEmpties,
+        ));
+    }
+    options.push(("Rescan directory".to_string(), Action::Rescan));
+    options.push(("Quit".to_string(), Action::Quit));
+
+    let labels: Vec<&str> = options.iter().map(|(label, _)| label.as_str()).collect();
+    loop {
+        let selection = Select::new()
+            .with_prompt("What would you like to do?")
+            .items(&labels)
+            .default(0)
+            .interact()
+            .map_err(io::Error::other)?;
 
-    match selection {
-        0 => {
-            let group_options: Vec<String> =
-                (1..=group_count).map(|i| format!("Group {}", i)).collect();
+        match options[selection].1 {
+            // Leaving the picker returns to the menu.
+            Action::ReviewGroup(_) => {
+                if let Some(group_idx) = pick_group(groups, decided)? {
+                    return Ok(Action::ReviewGroup(group_idx));
+                }
+            }
+            Action::RemoveDuplicateDir(_) => {
+                let tree_options: Vec<String> = (1..=tree_count)
+                    .map(|i| format!("Directory group {}", i))
+                    .collect();
+
+                let tree_idx = Select::new()
+                    .with_prompt("Select a directory group")
+                    .items(&tree_options)
+                    .default(0)
+                    .interact()
+                    .map_err(io::Error::other)?;
+
+                return Ok(Action::RemoveDuplicateDir(tree_idx));
+            }
+            action => return Ok(action),
+        }
+    }
+}
 
-            let group_idx = Select::new()
-                .with_prompt("Select a group to review")
-                .items(&group_options)
-                .default(0)
-                .interact()
-                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+/// Lets the user pick a duplicate group, narrowing the list with a filter.
+///
+/// Each entry shows the group's size, number of copies and first path. The
+/// first entry edits the filter (see [`GroupFilter`] for its syntax).
+///
+/// # Returns
+///
+/// The index of the chosen group, or `None` if the user pressed Esc.
+fn pick_group(groups: &[DuplicateGroup], decided: &[bool]) -> io::Result<Option<usize>> {
+    let mut filter = GroupFilter::default();
+    loop {
+        let matching: Vec<usize> = (0..groups.len())
+            .filter(|&i| filter.matches(&groups[i]))
+            .collect();
+
+        let mut labels = vec![if filter.is_empty() {
+            "Filter groups...".to_string()
+        } else {
+            format!(
+                "Filter groups... (showing {} of {} matching '{}')",
+                matching.len(),
+                groups.len(),
+                filter
+            )
+        }];
+        labels.extend(
+            matching
+                .iter()
+                .map(|&i| group_label(i + 1, &groups[i], decided[i])),
+        );
 
-            Ok(Action::ReviewGroup(group_idx))
+        let selection = Select::new()
+            .with_prompt("Select a group to review (Esc to go back)")
+            .items(&labels)
+            .default(if matching.is_empty() { 0 } else { 1 })
+            .max_length(20)
+            .interact_opt()
+            .map_err(io::Error::other)?;
+
+        match selection {
+            None => return Ok(None),
+            Some(0) => {
+                let text: String = Input::new()
+                    .with_prompt("Filter (text, .ext, >SIZE, <SIZE; empty for all)")
+                    .with_initial_text(filter.to_string())
+                    .allow_empty(true)
+                    .interact_text()
+                    .map_err(io::Error::other)?;
+                match text.parse() {
+                    Ok(parsed) => filter = parsed,
+                    Err(e) => eprintln!("Invalid filter: {}", e),
+                }
+            }
+            Some(entry) => return Ok(Some(matching[entry - 1])),
         }
-        1 => Ok(Action::DeleteAllDuplicates),
-        2 => Ok(Action::Rescan),
-        _ => Ok(Action::Quit),
     }
 }
 
+/// Describes a group in one line for the group picker.
+fn group_label(group_num: usize, group: &DuplicateGroup, decided: bool) -> String {
+    format!(
+        "Group {} - {} x {} - {}{}{}",
+        group_num,
+        group.paths.len(),
+        DuplicateStats::format_bytes(group.size),
+        group.paths[0].display(),
+        match group.paths.len() {
+            2 => " (+1 other)".to_string(),
+            n => format!(" (+{} others)", n - 1),
+        },
+        if decided { " [decided]" } else { "" }
+    )
+}
+
 /// Presents a duplicate group for review and file selection.
 ///
 /// Displays all files in the group and allows the user to select which
 /// files to delete using a multi-select interface. By default, all files
-/// except the first are pre-selected for deletion.
+/// except the one chosen by the keep policy are pre-selected for deletion,
+/// unless the group was decided earlier in a saved session, in which case
+/// that decision is pre-selected. Protected files are listed as locked and
+/// start unselected; a selection that includes one is rejected and asked
+/// for again.
 ///
 /// # Arguments
 ///
 /// * `group` - The duplicate group to review.
 /// * `group_num` - Display number for the group (1-indexed).
+/// * `policy` - Decides which file is kept by default and which are locked.
+/// * `decided` - Earlier decision to delete each file, if any.
 ///
 /// # Returns
 ///
-/// Indices of files selected for deletion, or an empty vector if cancelled.
-pub fn review_group(group: &DuplicateGroup, group_num: usize) -> io::Result<Vec<usize>> {
+/// Indices of files selected for deletion, which may be none, or `None` if
+/// the review was cancelled or nothing in the group can be deleted.
+pub fn review_group(
+    group: &DuplicateGroup,
+    group_num: usize,
+    policy: &KeepPolicy,
+    decided: Option<&[bool]>,
+) -> io::Result<Option<Vec<usize>>> {
     println!(
         "\nGroup {} - {} each",
         group_num,
         DuplicateStats::format_bytes(group.size)
     );
 
+    let keep = match decided {
+        Some(_) => None,
+        None => policy.choose(&group.paths),
+    };
+    let locked: Vec<bool> = group.paths.iter().map(|p| policy.is_protected(p)).collect();
+
     let path_options: Vec<String> = group
         .paths
         .iter()
         .enumerate()
         .map(|(i, p)| {
-            if i == 0 {
+            if locked[i] {
+                format!("{} (locked: protected)", p.display())
+            } else if Some(i) == keep {
                 format!("{} (will be kept)", p.display())
             } else {
                 p.display().to_string()
@@ -143,17 +473,62 @@ pub fn review_group(group: &DuplicateGroup, group_num: usize) -> io::Result<Vec<
         })
         .collect();
 
-    println!("\nSelect files to DELETE (the first file is kept by default):");
-    println!("Use SPACE to select/deselect, ENTER to confirm\n");
+    // Copies can be inspected before deciding which of them to delete.
+    let mut choices = vec!["Choose files to delete".to_string()];
+    choices.extend(
+        path_options
+            .iter()
+            .map(|option| format!("Preview {}", option)),
+    );
+    loop {
+        let choice = Select::new()
+            .with_prompt("Choose files to delete, or preview a copy first")
+            .items(&choices)
+            .default(0)
+            .interact()
+            .map_err(io::Error::other)?;
+        if choice == 0 {
+            break;
+        }
+        preview_file(&group.paths[choice - 1])?;
+    }
 
-    let defaults: Vec<bool> = (0..group.paths.len()).map(|i| i > 0).collect();
+    if locked.iter().all(|&l| l) {
+        println!("Every copy in this group is protected; nothing can be deleted.");
+        return Ok(None);
+    }
 
-    let selections = MultiSelect::new()
-        .with_prompt("Files to delete")
-        .items(&path_options)
-        .defaults(&defaults)
-        .interact()
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    println!("\nSelect files to DELETE (one file is kept by default):");
+    println!("Use SPACE to select/deselect, ENTER to confirm\n");
+
+    // Locked copies stay in the list but start unselected, and a selection
+    // that includes one is rejected.
+    let mut defaults: Vec<bool> = (0..group.paths.len())
+        .map(|i| match decided {
+            Some(decided) => decided[i] && !locked[i],
+            None => Some(i) != keep && !locked[i],
+        })
+        .collect();
+    let selections = loop {
+        let selections = MultiSelect::new()
+            .with_prompt("Files to delete")
+            .items(&path_options)
+            .defaults(&defaults)
+            .interact()
+            .map_err(io::Error::other)?;
+        let chosen_locked: Vec<usize> = selections.iter().copied().filter(|&i| locked[i]).collect();
+        if chosen_locked.is_empty() {
+            break selections;
+        }
+        println!("\nProtected files are locked and cannot be deleted:");
+        for &i in &chosen_locked {
+            println!("  {}", group.paths[i].display());
+        }
+        println!("They have been deselected; confirm the selection again.\n");
+        defaults = (0..group.paths.len())
+            .map(|i| selections.contains(&i) && !locked[i])
+            .collect();
+    };
 
     // Safety check: warn if deleting all copies
     if selections.len() == group.paths.len() {
@@ -162,91 +537,471 @@ pub fn review_group(group: &DuplicateGroup, group_num: usize) -> io::Result<Vec<
             .with_prompt("This will delete all copies. Are you sure?")
             .default(false)
             .interact()
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            .map_err(io::Error::other)?;
 
         if !proceed {
-            return Ok(vec![]);
+            return Ok(None);
+        }
+    }
+
+    Ok(Some(selections))
+}
+
+/// Shows a file's metadata and the start of its contents, then offers to
+/// open it in a pager or the default application.
+fn preview_file(path: &Path) -> io::Result<()> {
+    println!("\n{}", path.display());
+    match preview::metadata(path) {
+        Ok(lines) => lines.iter().for_each(|line| println!("  {}", line)),
+        Err(e) => {
+            eprintln!("Error reading '{}': {}", path.display(), e);
+            return Ok(());
         }
     }
+    match preview::contents(path) {
+        Ok(contents) => println!("\n{}", contents.trim_end()),
+        Err(e) => eprintln!("Error reading contents: {}", e),
+    }
+    println!();
 
-    Ok(selections)
+    let options = [
+        "Back to the group",
+        "Open in pager ($PAGER)",
+        "Open with the default application (xdg-open)",
+    ];
+    loop {
+        let choice = Select::new()
+            .with_prompt("Preview")
+            .items(&options)
+            .default(0)
+            .interact_opt()
+            .map_err(io::Error::other)?;
+        let opened = match choice {
+            Some(1) => preview::open_in_pager(path),
+            Some(2) => preview::open_with_default(path),
+            _ => return Ok(()),
+        };
+        if let Err(e) = opened {
+            eprintln!("Error opening '{}': {}", path.display(), e);
+        }
+    }
 }
 
-/// Deletes files at the specified indices within a duplicate group.
+/// Removes files at the specified indices within a duplicate group.
 ///
 /// # Arguments
 ///
 /// * `group` - The duplicate group containing the files.
 /// * `indices` - Indices of files to delete.
+/// * `policy` - Files it protects are refused.
+/// * `runner` - Carries out the removals, or only reports them in a dry run.
 ///
 /// # Returns
 ///
-/// Total bytes deleted, or an IO error.
-pub fn delete_files(group: &DuplicateGroup, indices: &[usize]) -> io::Result<u64> {
-    let mut deleted_bytes = 0u64;
+/// Total bytes deleted (or that would be deleted), or an IO error. Nothing
+/// is removed if any of the selected files is protected.
+pub fn delete_files(
+    group: &DuplicateGroup,
+    indices: &[usize],
+    policy: &KeepPolicy,
+    runner: &Runner,
+) -> io::Result<u64> {
+    for path in indices.iter().filter_map(|&i| group.paths.get(i)) {
+        policy.ensure_unprotected(path)?;
+    }
 
-    for &idx in indices {
-        if let Some(path) = group.paths.get(idx) {
-            match fs::remove_file(path) {
-                Ok(()) => {
-                    println!("  Deleted: {}", path.display());
-                    deleted_bytes += group.size;
-                }
-                Err(e) => {
-                    eprintln!("  Error deleting {}: {}", path.display(), e);
-                }
-            }
-        }
+    let mut plan = Plan::new();
+    plan.add_group(group, indices);
+
+    let report = runner.run(&plan);
+
+    if runner.is_dry_run() {
+        println!(
+            "\nDry run: would free {}{}",
+            DuplicateStats::format_bytes(report.freed),
+            pruned_summary(&report)
+        );
     }
 
-    Ok(deleted_bytes)
+    Ok(report.freed)
 }
 
-/// Deletes all duplicate files, keeping the first file in each group.
+/// Deletes all duplicate files, keeping one file in each group.
 ///
-/// Prompts for confirmation before proceeding. For each group, deletes
-/// all files except the first one.
+/// Prompts for confirmation before proceeding, unless this is a dry run.
+/// For each group, deletes all files except the one chosen by `policy`.
 ///
 /// # Arguments
 ///
 /// * `groups` - All duplicate groups to process.
+/// * `policy` - Decides which file in each group is kept.
+/// * `runner` - Carries out the removals, or only reports them in a dry run.
 ///
 /// # Returns
 ///
-/// Total bytes deleted, or 0 if cancelled.
-pub fn delete_all_duplicates(groups: &[DuplicateGroup]) -> io::Result<u64> {
-    let total_to_delete: usize = groups.iter().map(|g| g.paths.len() - 1).sum();
+/// Total bytes deleted (or that would be deleted), or 0 if cancelled.
+pub fn delete_all_duplicates(
+    groups: &[DuplicateGroup],
+    policy: &KeepPolicy,
+    runner: &Runner,
+) -> io::Result<u64> {
+    let plan = Plan::from_policy(groups, policy);
+    let total_to_delete = plan.operations.len();
 
     println!(
-        "\nThis will delete {} file(s), keeping the first file from each group.",
-        total_to_delete
+        "\nThis will {} {} file(s) ({}), keeping one file from each group.",
+        runner.destination(),
+        total_to_delete,
+        DuplicateStats::format_bytes(plan.total_bytes())
     );
 
-    let proceed = Confirm::new()
-        .with_prompt("Are you sure you want to proceed?")
-        .default(false)
-        .interact()
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    if !runner.is_dry_run() {
+        let proceed = Confirm::new()
+            .with_prompt("Are you sure you want to proceed?")
+            .default(false)
+            .interact()
+            .map_err(io::Error::other)?;
 
-    if !proceed {
-        println!("Cancelled.");
+        if !proceed {
+            println!("Cancelled.");
+            return Ok(0);
+        }
+    }
+
+    Ok(apply_plan(&plan, runner))
+}
+
+/// Deletes the duplicates inside a directory the user picks, keeping their
+/// copies outside it.
+///
+/// Directories are listed by the space they would free. The affected groups
+/// are previewed, and confirmation is asked for unless this is a dry run.
+///
+/// # Returns
+///
+/// Total bytes deleted (or that would be deleted), or 0 if cancelled.
+pub fn delete_from_directory(
+    groups: &[DuplicateGroup],
+    policy: &KeepPolicy,
+    runner: &Runner,
+) -> io::Result<u64> {
+    let choices = dir_choices(groups, policy);
+    if choices.is_empty() {
+        println!("\nNo directory holds duplicates that have a copy elsewhere.");
         return Ok(0);
     }
 
-    let mut total_deleted = 0u64;
+    let labels: Vec<String> = choices
+        .iter()
+        .map(|c| {
+            format!(
+                "{}/ - {} file(s) in {} group(s), {}",
+                c.dir.display(),
+                c.files,
+                c.groups,
+                DuplicateStats::format_bytes(c.bytes)
+            )
+        })
+        .collect();
+    let selection = Select::new()
+        .with_prompt("Select a directory to clear of duplicates (Esc to go back)")
+        .items(&labels)
+        .default(0)
+        .max_length(20)
+        .interact_opt()
+        .map_err(io::Error::other)?;
+    let Some(selection) = selection else {
+        return Ok(0);
+    };
+
+    let dir = &choices[selection].dir;
+    let (plan, affected) = plan_for_dir(groups, dir, policy);
+    let victims: HashSet<&Path> = plan
+        .operations
+        .iter()
+        .map(|op| op.victim.as_path())
+        .collect();
 
-    for group in groups {
-        let indices: Vec<usize> = (1..group.paths.len()).collect();
-        total_deleted += delete_files(group, &indices)?;
+    println!(
+        "\nRemoving the duplicates under {}/ affects {} group(s):",
+        dir.display(),
+        affected.len()
+    );
+    for &index in affected.iter().take(PREVIEW_GROUPS) {
+        let group = &groups[index];
+        println!(
+            "\nGroup {} - {} each",
+            index + 1,
+            DuplicateStats::format_bytes(group.size)
+        );
+        for path in &group.paths {
+            let verb = if victims.contains(path.as_path()) {
+                "remove"
+            } else {
+                "keep  "
+            };
+            println!("  {} {}", verb, path.display());
+        }
+    }
+    if affected.len() > PREVIEW_GROUPS {
+        println!(
+            "\n  ... and {} more group(s)",
+            affected.len() - PREVIEW_GROUPS
+        );
     }
 
     println!(
-        "\nDeleted {} file(s), recovered {}",
-        total_to_delete,
-        DuplicateStats::format_bytes(total_deleted)
+        "\nThis will {} {} file(s) ({}).",
+        runner.destination(),
+        plan.operations.len(),
+        DuplicateStats::format_bytes(plan.total_bytes())
+    );
+
+    if !runner.is_dry_run() {
+        let proceed = Confirm::new()
+            .with_prompt("Are you sure you want to proceed?")
+            .default(false)
+            .interact()
+            .map_err(io::Error::other)?;
+
+        if !proceed {
+            println!("Cancelled.");
+            return Ok(0);
+        }
+    }
+
+    Ok(apply_plan(&plan, runner))
+}
+
+/// Runs an already confirmed plan and prints what it freed.
+///
+/// # Returns
+///
+/// Total bytes deleted (or that would be deleted).
+pub fn apply_plan(plan: &Plan, runner: &Runner) -> u64 {
+    let report = runner.run(plan);
+
+    if runner.is_dry_run() {
+        println!(
+            "\nDry run: {} file(s) would be removed, freeing {}{}",
+            report.removed.len(),
+            DuplicateStats::format_bytes(report.freed),
+            pruned_summary(&report)
+        );
+    } else {
+        println!(
+            "\n{} {} file(s), recovered {}{}",
+            runner.verb(),
+            report.removed.len(),
+            DuplicateStats::format_bytes(report.freed),
+            pruned_summary(&report)
+        );
+    }
+
+    report.freed
+}
+
+/// Describes the empty directories a run removed, for summary lines.
+fn pruned_summary(report: &RunReport) -> String {
+    match report.pruned_dirs.len() {
+        0 => String::new(),
+        n => format!(
+            ", {} empty director{} removed",
+            n,
+            if n == 1 { "y" } else { "ies" }
+        ),
+    }
+}
+
+/// Removes all but one copy of a duplicated directory tree.
+///
+/// Shows the identical directories and asks which one to keep, defaulting to
+/// the one chosen by `policy`. Every other copy is removed file by file, each
+/// file verified against its twin in the kept directory, and then the copy's
+/// directories themselves are removed. Copies containing protected files are
+/// skipped.
+///
+/// # Arguments
+///
+/// * `tree` - The duplicate directory group.
+/// * `tree_num` - Display number for the group (1-indexed).
+/// * `groups` - File duplicate groups from the same scan.
+/// * `policy` - Decides the default copy to keep and which files are protected.
+/// * `runner` - Carries out the removals, or only reports them in a dry run.
+///
+/// # Returns
+///
+/// Total bytes removed (or that would be removed), or 0 if cancelled.
+pub fn remove_duplicate_dir(
+    tree: &DirGroup,
+    tree_num: usize,
+    groups: &[DuplicateGroup],
+    policy: &KeepPolicy,
+    runner: &Runner,
+) -> io::Result<u64> {
+    println!(
+        "\nDirectory group {} - {} in {} file(s)",
+        tree_num,
+        DuplicateStats::format_bytes(tree.size),
+        tree.file_count
     );
 
-    Ok(total_deleted)
+    let labels: Vec<String> = tree
+        .dirs
+        .iter()
+        .map(|d| format!("{}/", d.display()))
+        .collect();
+    let keep = Select::new()
+        .with_prompt("Directory to KEEP (all other copies are removed)")
+        .items(&labels)
+        .default(policy.choose(&tree.dirs).unwrap_or(0))
+        .interact()
+        .map_err(io::Error::other)?;
+
+    if !runner.is_dry_run() {
+        let proceed = Confirm::new()
+            .with_prompt(format!(
+                "This will {} {} other directory tree(s). Are you sure?",
+                runner.destination(),
+                tree.dirs.len() - 1
+            ))
+            .default(false)
+            .interact()
+            .map_err(io::Error::other)?;
+
+        if !proceed {
+            println!("Cancelled.");
+            return Ok(0);
+        }
+    }
+
+    let mut freed = 0;
+    for remove in (0..tree.dirs.len()).filter(|&i| i != keep) {
+        let dir = &tree.dirs[remove];
+        let plan = match tree.plan_removal(keep, remove, groups) {
+            Ok(plan) => plan,
+            Err(e) => {
+                eprintln!("  Skipped {}: {}", dir.display(), e);
+                continue;
+            }
+        };
+        if let Some(op) = plan
+            .operations
+            .iter()
+            .find(|op| policy.is_protected(&op.victim))
+        {
+            eprintln!(
+                "  Skipped {}: contains protected file {}",
+                dir.display(),
+                op.victim.display()
+            );
+            continue;
+        }
+
+        let report = runner.run(&plan);
+        freed += report.freed;
+        if report.removed.len() == plan.operations.len() && !report.pruned_dirs.contains(dir) {
+            runner.remove_empty_dirs(std::slice::from_ref(dir));
+        }
+    }
+
+    if runner.is_dry_run() {
+        println!(
+            "\nDry run: would free {}",
+            DuplicateStats::format_bytes(freed)
+        );
+    } else {
+        println!("\nRecovered {}", DuplicateStats::format_bytes(freed));
+    }
+
+    Ok(freed)
+}
+
+/// Deletes the empty files and empty directory trees found by the scan.
+///
+/// Prompts for confirmation before proceeding, unless this is a dry run.
+/// Empty files go through `runner` like any other removal; directory trees
+/// are removed outright. Anything protected by `policy` is left alone.
+///
+/// # Returns
+///
+/// The number of files and directory trees removed (or that would be
+/// removed), or 0 if cancelled.
+pub fn delete_empties(
+    empties: &Empties,
+    policy: &KeepPolicy,
+    runner: &Runner,
+) -> io::Result<usize> {
+    let files: Vec<_> = empties
+        .files
+        .iter()
+        .filter(|f| !policy.is_protected(&f.path))
+        .cloned()
+        .collect();
+    let dirs: Vec<_> = empties
+        .dirs
+        .iter()
+        .filter(|d| !policy.is_protected(d))
+        .cloned()
+        .collect();
+
+    println!(
+        "\nThis will {} {} empty file(s) and remove {} empty directory tree(s).",
+        runner.destination(),
+        files.len(),
+        dirs.len()
+    );
+
+    if !runner.is_dry_run() {
+        let proceed = Confirm::new()
+            .with_prompt("Are you sure you want to proceed?")
+            .default(false)
+            .interact()
+            .map_err(io::Error::other)?;
+
+        if !proceed {
+            println!("Cancelled.");
+            return Ok(0);
+        }
+    }
+
+    let mut plan = Plan::new();
+    plan.add_empty_files(&files);
+    let report = runner.run(&plan);
+    let removed_dirs = runner.remove_empty_dirs(&dirs);
+
+    let removed = report.removed.len() + removed_dirs.len();
+    if runner.is_dry_run() {
+        println!(
+            "\nDry run: {} empty file(s) and {} directory tree(s) would be removed{}",
+            report.removed.len(),
+            removed_dirs.len(),
+            pruned_summary(&report)
+        );
+    } else {
+        println!(
+            "\n{} {} empty file(s), removed {} directory tree(s){}",
+            runner.verb(),
+            report.removed.len(),
+            removed_dirs.len(),
+            pruned_summary(&report)
+        );
+    }
+
+    Ok(removed)
+}
+
+/// Asks whether to continue with the groups found before an interrupted scan.
+///
+/// # Returns
+///
+/// `true` if the user wants to see them, `false` to exit.
+pub fn prompt_show_partial() -> io::Result<bool> {
+    Confirm::new()
+        .with_prompt("Show the groups confirmed so far?")
+        .default(true)
+        .interact()
+        .map_err(io::Error::other)
 }
 
 /// Prompts the user to rescan the directory for verification.
@@ -259,5 +1014,5 @@ pub fn prompt_rescan() -> io::Result<bool> {
         .with_prompt("Would you like to rescan to verify no duplicates remain?")
         .default(true)
         .interact()
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+        .map_err(io::Error::other)
 }
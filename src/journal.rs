@@ -0,0 +1,503 @@
+//! Undo journal for destructive actions.
+//!
+//! Every file the tool removes is appended to a journal together with the
+//! copy that was kept. Because both files had identical content, a removed
+//! file can be recreated later by copying its kept twin back into place and
+//! restoring the original permissions and modification time.
+//!
+//! The journal is a tab-separated text file with one entry per line:
+//! `<unix seconds>\t<action>\t<size>\t<hash>\t<mode>\t<mtime>\t<removed>\t<kept>`,
+//! where `mode` is octal, `mtime` is `seconds.nanoseconds` and both paths are
+//! percent-encoded. An empty `kept` field means no copy was kept.
+
+use std::env;
+use std::fs::{self, OpenOptions, Permissions};
+use std::io::{self, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::format::{decode_path, encode_path};
+use crate::scanner::hash_file;
+
+/// What happened to a removed file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JournalAction {
+    /// Moved to the freedesktop.org Trash.
+    Trash,
+    /// Moved into a quarantine directory.
+    Quarantine,
+    /// Deleted permanently.
+    Delete,
+}
+
+impl JournalAction {
+    fn as_str(self) -> &'static str {
+        match self {
+            JournalAction::Trash => "trash",
+            JournalAction::Quarantine => "quarantine",
+            JournalAction::Delete => "delete",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "trash" => Some(JournalAction::Trash),
+            "quarantine" => Some(JournalAction::Quarantine),
+            "delete" => Some(JournalAction::Delete),
+            _ => None,
+        }
+    }
+}
+
+/// A single destructive action recorded in the journal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JournalEntry {
+    /// When the action happened, in seconds since the Unix epoch.
+    pub timestamp: u64,
+
+    /// What was done to the removed file.
+    pub action: JournalAction,
+
+    /// Path of the file that was removed.
+    pub removed: PathBuf,
+
+    /// Path of the identical copy that was kept, if any.
+    pub kept: Option<PathBuf>,
+
+    /// File size in bytes.
+    pub size: u64,
+
+    /// Content hash shared by the removed and kept files.
+    pub hash: String,
+
+    /// Unix permission bits of the removed file.
+    pub mode: u32,
+
+    /// Modification time of the removed file.
+    pub modified: SystemTime,
+}
+
+/// An append-only journal file.
+#[derive(Debug, Clone)]
+pub struct Journal {
+    /// Location of the journal file.
+    pub path: PathBuf,
+}
+
+impl Journal {
+    /// Creates a journal stored at `path`.
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// Returns the default journal location,
+    /// `$XDG_STATE_HOME/find_duplicates/journal`.
+    pub fn default_path() -> Option<PathBuf> {
+        Some(state_dir()?.join("journal"))
+    }
+
+    /// Appends an entry and syncs it to disk.
+    pub fn append(&self, entry: &JournalEntry) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+
+        file.write_all(format_entry(entry).as_bytes())?;
+        file.sync_data()
+    }
+
+    /// Removes the most recent entry equal to `entry`, for removals that
+    /// were journaled but then failed.
+    pub fn retract(&self, entry: &JournalEntry) -> io::Result<()> {
+        let mut entries = self.read()?;
+        if let Some(pos) = entries.iter().rposition(|e| e == entry) {
+            entries.remove(pos);
+            self.write(&entries)?;
+        }
+        Ok(())
+    }
+
+    /// Reads all entries, oldest first.
+    ///
+    /// A missing journal is treated as empty.
+    pub fn read(&self) -> io::Result<Vec<JournalEntry>> {
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        contents
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                parse_entry(line).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("malformed journal line: {}", line),
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// Replaces the journal contents with `entries`.
+    fn write(&self, entries: &[JournalEntry]) -> io::Result<()> {
+        let contents: String = entries.iter().map(format_entry).collect();
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+
+        fs::write(&tmp, contents)?;
+        fs::rename(tmp, &self.path)
+    }
+}
+
+/// Returns the directory for the tool's state files,
+/// `$XDG_STATE_HOME/find_duplicates`.
+pub fn state_dir() -> Option<PathBuf> {
+    let state_dir = match env::var_os("XDG_STATE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".local/state"),
+    };
+
+    Some(state_dir.join("find_duplicates"))
+}
+
+/// Restores removed files from their kept twins.
+///
+/// Entries are undone newest first. Restored entries are dropped from the
+/// journal; entries that cannot be restored are reported and kept.
+///
+/// # Arguments
+///
+/// * `journal` - Journal to undo.
+/// * `last` - Only undo this many of the most recent entries, or all if `None`.
+/// * `dry_run` - Only report which files would be restored, leaving the
+///   disk and the journal untouched.
+///
+/// # Returns
+///
+/// The number of files restored, or that would be restored in a dry run, or
+/// an IO error if the journal is unreadable.
+pub fn undo(journal: &Journal, last: Option<usize>, dry_run: bool) -> io::Result<usize> {
+    let mut entries = journal.read()?;
+    let split = entries.len() - last.unwrap_or(entries.len()).min(entries.len());
+    let candidates = entries.split_off(split);
+    let mut restored = 0;
+    let mut failed = Vec::new();
+
+    if dry_run {
+        for entry in candidates.iter().rev() {
+            match check_entry(entry) {
+                Ok(_) => {
+                    println!("  Would restore: {}", entry.removed.display());
+                    restored += 1;
+                }
+                Err(e) => eprintln!("  Cannot restore {}: {}", entry.removed.display(), e),
+            }
+        }
+        return Ok(restored);
+    }
+
+    for entry in candidates.into_iter().rev() {
+        match restore_entry(&entry) {
+            Ok(()) => {
+                println!("  Restored: {}", entry.removed.display());
+                restored += 1;
+            }
+            Err(e) => {
+                eprintln!("  Cannot restore {}: {}", entry.removed.display(), e);
+                failed.push(entry);
+            }
+        }
+    }
+
+    // Nothing to drop from the journal if every entry failed.
+    if restored > 0 {
+        failed.reverse();
+        entries.extend(failed);
+        journal.write(&entries)?;
+    }
+
+    Ok(restored)
+}
+
+/// Checks that a removed file can be recreated.
+///
+/// The kept file is re-hashed so that a copy is only made when its content
+/// still matches what was removed. Empty files need no twin.
+///
+/// # Returns
+///
+/// The kept twin to copy, `None` for an empty file, or an error explaining
+/// why the file cannot be restored.
+fn check_entry(entry: &JournalEntry) -> io::Result<Option<&Path>> {
+    if fs::symlink_metadata(&entry.removed).is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "a file already exists at this path",
+        ));
+    }
+
+    let kept = match (entry.kept.as_deref(), entry.size) {
+        (Some(kept), _) => Some(kept),
+        (None, 0) => None,
+        (None, _) => {
+            let hint = match entry.action {
+                JournalAction::Trash => "no copy was kept; restore it from the Trash",
+                JournalAction::Quarantine => {
+                    "no copy was kept; restore it with `quarantine restore`"
+                }
+                JournalAction::Delete => "no copy was kept and the file was deleted permanently",
+            };
+            return Err(io::Error::new(io::ErrorKind::NotFound, hint));
+        }
+    };
+
+    if let Some(kept) = kept {
+        if fs::metadata(kept)?.len() != entry.size || hash_file(kept)? != entry.hash {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("kept copy {} has changed", kept.display()),
+            ));
+        }
+    }
+    Ok(kept)
+}
+
+/// Recreates a removed file by copying its kept twin, or from nothing for
+/// an empty file.
+///
+/// The file is created here and never replaces one that appeared at its
+/// path in the meantime; if filling it in fails, only the file created here
+/// is removed again.
+fn restore_entry(entry: &JournalEntry) -> io::Result<()> {
+    let kept = check_entry(entry)?;
+    if let Some(parent) = entry.removed.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut file = fs::File::create_new(&entry.removed)?;
+    let filled = fill_restored(&mut file, kept, entry);
+    if filled.is_err() {
+        let _ = fs::remove_file(&entry.removed);
+    }
+    filled
+}
+
+/// Copies the kept twin, if any, into a newly created `file` and gives it
+/// the recorded modification time and permissions.
+fn fill_restored(file: &mut fs::File, kept: Option<&Path>, entry: &JournalEntry) -> io::Result<()> {
+    if let Some(kept) = kept {
+        io::copy(&mut fs::File::open(kept)?, file)?;
+    }
+    // The mtime is set before the permissions, which may make the file read-only.
+    file.set_modified(entry.modified)?;
+    file.set_permissions(Permissions::from_mode(entry.mode))
+}
+
+fn parse_entry(line: &str) -> Option<JournalEntry> {
+    let mut fields = line.split('\t');
+    let timestamp = fields.next()?.parse().ok()?;
+    let action = JournalAction::parse(fields.next()?)?;
+    let size = fields.next()?.parse().ok()?;
+    let hash = fields.next()?.to_string();
+    let mode = u32::from_str_radix(fields.next()?, 8).ok()?;
+
+    let (secs, nanos) = fields.next()?.split_once('.')?;
+    let modified = UNIX_EPOCH + Duration::new(secs.parse().ok()?, nanos.parse().ok()?);
+
+    let removed = decode_path(fields.next()?)?;
+    let kept = match fields.next()? {
+        "" => None,
+        encoded => Some(decode_path(encoded)?),
+    };
+
+    fields.next().is_none().then_some(JournalEntry {
+        timestamp,
+        action,
+        removed,
+        kept,
+        size,
+        hash,
+        mode,
+        modified,
+    })
+}
+
+fn format_entry(entry: &JournalEntry) -> String {
+    let mtime = entry
+        .modified
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+
+    format!(
+        "{}\t{}\t{}\t{}\t{:o}\t{}.{:09}\t{}\t{}\n",
+        entry.timestamp,
+        entry.action.as_str(),
+        entry.size,
+        entry.hash,
+        entry.mode,
+        mtime.as_secs(),
+        mtime.subsec_nanos(),
+        encode_path(&entry.removed),
+        entry.kept.as_deref().map(encode_path).unwrap_or_default()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::MetadataExt;
+    use tempfile::TempDir;
+
+    fn entry_for(removed: &Path, kept: &Path) -> JournalEntry {
+        JournalEntry {
+            timestamp: 1_700_000_000,
+            action: JournalAction::Delete,
+            removed: removed.to_path_buf(),
+            kept: Some(kept.to_path_buf()),
+            size: 5,
+            hash: hash_file(kept).unwrap(),
+            mode: 0o440,
+            modified: UNIX_EPOCH + Duration::new(1_600_000_000, 123_456_789),
+        }
+    }
+
+    #[test]
+    fn test_journal_roundtrip() {
+        let dir = TempDir::new().unwrap();
+        let kept = dir.path().join("kept file.txt");
+        fs::write(&kept, b"hello").unwrap();
+
+        let journal = Journal::new(dir.path().join("state/journal"));
+        let mut entry = entry_for(&dir.path().join("gone.txt"), &kept);
+        journal.append(&entry).unwrap();
+        entry.kept = None;
+        journal.append(&entry).unwrap();
+
+        let entries = journal.read().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0], entry_for(&dir.path().join("gone.txt"), &kept));
+        assert_eq!(entries[1].kept, None);
+    }
+
+    #[test]
+    fn test_undo_restores_from_kept_twin() {
+        let dir = TempDir::new().unwrap();
+        let kept = dir.path().join("a.txt");
+        let removed = dir.path().join("sub/b.txt");
+        fs::write(&kept, b"hello").unwrap();
+
+        let journal = Journal::new(dir.path().join("journal"));
+        journal.append(&entry_for(&removed, &kept)).unwrap();
+
+        assert_eq!(undo(&journal, None, false).unwrap(), 1);
+
+        let meta = fs::metadata(&removed).unwrap();
+        assert_eq!(fs::read(&removed).unwrap(), b"hello");
+        assert_eq!(meta.permissions().mode() & 0o777, 0o440);
+        assert_eq!(
+            meta.modified().unwrap(),
+            UNIX_EPOCH + Duration::new(1_600_000_000, 123_456_789)
+        );
+        assert!(journal.read().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_undo_dry_run_changes_nothing() {
+        let dir = TempDir::new().unwrap();
+        let kept = dir.path().join("a.txt");
+        let removed = dir.path().join("b.txt");
+        fs::write(&kept, b"hello").unwrap();
+
+        let journal = Journal::new(dir.path().join("journal"));
+        journal.append(&entry_for(&removed, &kept)).unwrap();
+
+        assert_eq!(undo(&journal, None, true).unwrap(), 1);
+        assert!(!removed.exists());
+        assert_eq!(journal.read().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_undo_refuses_changed_keeper() {
+        let dir = TempDir::new().unwrap();
+        let kept = dir.path().join("a.txt");
+        let removed = dir.path().join("b.txt");
+        fs::write(&kept, b"hello").unwrap();
+
+        let journal = Journal::new(dir.path().join("journal"));
+        journal.append(&entry_for(&removed, &kept)).unwrap();
+        fs::write(&kept, b"HELLO").unwrap();
+        let inode = |path: &Path| fs::metadata(path).unwrap().ino();
+        let before = inode(&journal.path);
+
+        assert_eq!(undo(&journal, None, false).unwrap(), 0);
+        assert!(!removed.exists());
+        assert_eq!(journal.read().unwrap().len(), 1);
+        // A journal with nothing restored is not rewritten.
+        assert_eq!(inode(&journal.path), before);
+    }
+
+    #[test]
+    fn test_undo_recreates_empty_file_without_twin() {
+        let dir = TempDir::new().unwrap();
+        let removed = dir.path().join("zero.txt");
+        fs::write(&removed, b"").unwrap();
+        let mut entry = entry_for(&removed, &removed);
+        fs::remove_file(&removed).unwrap();
+        entry.kept = None;
+        entry.size = 0;
+
+        let journal = Journal::new(dir.path().join("journal"));
+        journal.append(&entry).unwrap();
+
+        assert_eq!(undo(&journal, None, false).unwrap(), 1);
+        assert_eq!(fs::metadata(&removed).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_retract_drops_latest_matching_entry() {
+        let dir = TempDir::new().unwrap();
+        let kept = dir.path().join("a.txt");
+        fs::write(&kept, b"hello").unwrap();
+        let journal = Journal::new(dir.path().join("journal"));
+        let first = entry_for(&dir.path().join("b.txt"), &kept);
+        let second = entry_for(&dir.path().join("c.txt"), &kept);
+        journal.append(&first).unwrap();
+        journal.append(&second).unwrap();
+
+        journal.retract(&second).unwrap();
+
+        assert_eq!(journal.read().unwrap(), vec![first]);
+    }
+
+    #[test]
+    fn test_undo_last_only_restores_newest() {
+        let dir = TempDir::new().unwrap();
+        let kept = dir.path().join("a.txt");
+        fs::write(&kept, b"hello").unwrap();
+
+        let journal = Journal::new(dir.path().join("journal"));
+        journal
+            .append(&entry_for(&dir.path().join("old.txt"), &kept))
+            .unwrap();
+        journal
+            .append(&entry_for(&dir.path().join("new.txt"), &kept))
+            .unwrap();
+
+        assert_eq!(undo(&journal, Some(1), false).unwrap(), 1);
+
+        assert!(dir.path().join("new.txt").exists());
+        assert!(!dir.path().join("old.txt").exists());
+        let remaining = journal.read().unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].removed, dir.path().join("old.txt"));
+    }
+}
@@ -4,56 +4,938 @@
 //! using MD5 hashing, and provides an interactive interface for reviewing and
 //! deleting duplicates.
 
+mod actions;
+mod bulk;
+mod cancel;
+mod checkpoint;
+mod config;
 mod duplicates;
+mod filter;
+mod format;
+mod index;
 mod interactive;
+mod journal;
+mod manifest;
+mod overlap;
+mod policy;
+mod preview;
+mod progress;
+mod prune;
+mod quarantine;
 mod scanner;
+mod script;
+mod session;
+#[cfg(test)]
+mod test_util;
+mod trash;
+mod trees;
+mod tui;
 
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::ops::ControlFlow;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
 use std::process;
+use std::time::Duration;
 
-use clap::Parser;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand};
 
-use duplicates::{find_duplicates, DuplicateStats};
+use actions::{Executor, Plan, RemovalMode, Runner, EMPTY_HASH};
+use cancel::CancelGuard;
+use checkpoint::{Checkpoint, CheckpointWriter};
+use duplicates::{
+    find_duplicates, sort_groups, DuplicateGroup, DuplicateStats, GroupOrder, PathOrder,
+};
+use index::HashIndex;
 use interactive::{
-    delete_all_duplicates, delete_files, display_summary, prompt_rescan, review_group,
-    show_main_menu, Action,
+    apply_plan, confirm_purge, delete_all_duplicates, delete_empties, delete_files,
+    delete_from_directory, display_empties, display_overlaps, display_purge, display_query,
+    display_summary, display_trees, display_verification, prompt_rescan, prompt_show_partial,
+    remove_duplicate_dir, review_group, show_main_menu, Action,
 };
-use scanner::scan_directory;
+use journal::Journal;
+use overlap::{find_overlaps, DirTotals};
+use policy::{KeepPolicy, KeepRule};
+use progress::ProgressReporter;
+use prune::{PruneMode, Pruner};
+use quarantine::Quarantine;
+use scanner::{group_by_hash, resume_scan, scan_directory, Empties, FileInfo, Progress};
+use script::{MoveTarget, ScriptCommand};
+use session::Session;
+use trees::{find_duplicate_trees, DirGroup};
 
 /// Command-line arguments.
 #[derive(Parser, Debug)]
 #[command(name = "find_duplicates")]
 #[command(version)]
 #[command(about = "Find and manage duplicate files in a directory")]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
-    /// Directory to scan for duplicates
-    #[arg(value_name = "DIRECTORY")]
-    directory: PathBuf,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Directory to scan for duplicates (optional when reopening a session)
+    #[arg(value_name = "DIRECTORY", required_unless_present = "session")]
+    directory: Option<PathBuf>,
+
+    /// Delete files permanently instead of moving them to the Trash
+    #[arg(long, conflicts_with = "quarantine")]
+    permanent: bool,
+
+    /// Move duplicates into this directory instead of the Trash
+    #[arg(long, value_name = "DIR")]
+    quarantine: Option<PathBuf>,
+
+    /// Order in which duplicate groups are listed and numbered
+    #[arg(long, value_name = "ORDER", value_enum, default_value_t = GroupOrder::Wasted)]
+    sort_groups: GroupOrder,
+
+    /// Order of the paths within each group
+    #[arg(long, value_name = "ORDER", value_enum, default_value_t = PathOrder::Name)]
+    sort_paths: PathOrder,
+
+    /// Rule choosing which copy to keep; repeat to add tie-breakers.
+    /// One of: oldest, newest, shortest-path, longest-path, shallowest,
+    /// deepest, prefer:<DIR>, match:<REGEX>
+    #[arg(long, value_name = "RULE")]
+    keep: Vec<KeepRule>,
+
+    /// Never remove files under this directory; repeat for several
+    #[arg(long, value_name = "DIR")]
+    protect: Vec<PathBuf>,
+
+    /// Show what would be removed without touching any files
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Number of partially overlapping directory pairs to list (0 to skip)
+    #[arg(long, value_name = "N", default_value_t = 10)]
+    overlaps: usize,
+
+    /// Remove directories left empty by removals; `all` also removes
+    /// pre-existing empty directories under DIRECTORY
+    #[arg(
+        long,
+        value_name = "WHICH",
+        value_enum,
+        num_args = 0..=1,
+        default_missing_value = "emptied"
+    )]
+    prune_empty_dirs: Option<PruneMode>,
+
+    /// Re-hash each file before removing it, not only re-check size and mtime
+    #[arg(long)]
+    verify_hash: bool,
+
+    /// Write a shell script performing the cleanup instead of acting directly
+    #[arg(long, value_name = "FILE")]
+    script: Option<PathBuf>,
+
+    /// Command the generated script uses for each duplicate (`mv` needs --quarantine)
+    #[arg(long, value_name = "COMMAND", value_enum, default_value_t = ScriptCommand::Rm, requires = "script")]
+    script_command: ScriptCommand,
+
+    /// Journal file recording every removal [default: ~/.local/state/find_duplicates/journal]
+    #[arg(long, value_name = "FILE")]
+    journal: Option<PathBuf>,
+
+    /// Continue an interrupted scan from its checkpoint
+    #[arg(long)]
+    resume: bool,
+
+    /// Checkpoint file for resuming interrupted scans
+    /// [default: ~/.local/state/find_duplicates/checkpoints/<id of DIRECTORY>]
+    #[arg(long, value_name = "FILE")]
+    checkpoint: Option<PathBuf>,
+
+    /// Save the scan results and review decisions to FILE, or reopen the
+    /// review saved there without scanning again
+    #[arg(long, value_name = "FILE")]
+    session: Option<PathBuf>,
+
+    /// Use the hashes in this md5sum-format manifest instead of reading
+    /// files not changed since it was written; repeat for several
+    #[arg(long, value_name = "FILE")]
+    import_manifest: Vec<PathBuf>,
+
+    /// Hash every file and write the hashes to FILE in md5sum format
+    /// instead of opening the menu
+    #[arg(long, value_name = "FILE")]
+    export_manifest: Option<PathBuf>,
+
+    /// Apply this profile from the configuration files
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
+
+    /// Record the scanned files in the hash index for `query` instead of
+    /// opening the menu
+    #[arg(long)]
+    update_index: bool,
+
+    /// Hash index to update [default: ~/.local/state/find_duplicates/index]
+    #[arg(long, value_name = "FILE", requires = "update_index")]
+    index: Option<PathBuf>,
+}
+
+/// Subcommands that operate on the results of earlier runs.
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Restore or purge files moved aside with `--quarantine`
+    Quarantine {
+        #[command(subcommand)]
+        action: QuarantineAction,
+    },
+    /// Restore removed files from the identical copies that were kept
+    Undo {
+        /// Journal file to undo [default: the `journal` setting, or
+        /// ~/.local/state/find_duplicates/journal]
+        #[arg(long, value_name = "FILE")]
+        journal: Option<PathBuf>,
+
+        /// Apply this profile from the user's configuration file
+        #[arg(long, value_name = "NAME")]
+        profile: Option<String>,
+
+        /// Only undo the N most recent removals
+        #[arg(long, value_name = "N")]
+        last: Option<usize>,
+
+        /// List the files that would be restored without restoring them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Check a directory against an md5sum-format manifest, reporting
+    /// missing, new and changed files
+    Verify {
+        /// Manifest to check against, such as one from --export-manifest
+        #[arg(value_name = "MANIFEST")]
+        manifest: PathBuf,
+
+        /// Directory the manifest describes
+        #[arg(value_name = "DIRECTORY")]
+        directory: PathBuf,
+    },
+    /// Report which files already have a copy in the hash index built with
+    /// --update-index; exits 0 if any does, 1 if none does
+    Query {
+        /// Files to look up; directories are searched recursively
+        #[arg(value_name = "PATH", required = true)]
+        paths: Vec<PathBuf>,
+
+        /// Hash index to query [default: ~/.local/state/find_duplicates/index]
+        #[arg(long, value_name = "FILE")]
+        index: Option<PathBuf>,
+    },
+}
+
+/// Operations on an existing quarantine directory.
+#[derive(Subcommand, Debug)]
+enum QuarantineAction {
+    /// Move every quarantined file back to its original location
+    Restore {
+        /// Quarantine directory to restore from
+        #[arg(value_name = "DIR")]
+        dir: PathBuf,
+    },
+    /// Permanently delete quarantined files older than the grace period
+    Purge {
+        /// Quarantine directory to purge
+        #[arg(value_name = "DIR")]
+        dir: PathBuf,
+
+        /// Grace period in days; newer files are kept
+        #[arg(long, value_name = "DAYS", default_value_t = 30)]
+        older_than: u64,
+
+        /// Delete without asking for confirmation
+        #[arg(long, conflicts_with = "dry_run")]
+        yes: bool,
+
+        /// List the files that would be purged without deleting them
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+/// Everything a scan found that can be acted on.
+struct Findings {
+    /// Groups of identical files.
+    groups: Vec<DuplicateGroup>,
+    /// Groups of identical directory trees.
+    trees: Vec<DirGroup>,
+    /// Empty files and directory trees.
+    empties: Empties,
+    /// Content hash of every file hashed, by path.
+    hashes: BTreeMap<PathBuf, String>,
+}
+
+impl Findings {
+    fn is_empty(&self) -> bool {
+        self.groups.is_empty() && self.trees.is_empty() && self.empties.is_empty()
+    }
 }
 
 /// Scans a directory for duplicates and displays the results.
 ///
 /// This function handles the complete scan workflow: directory traversal,
-/// duplicate detection, and summary display.
-fn scan_and_display(dir: &PathBuf) -> Vec<duplicates::DuplicateGroup> {
+/// duplicate detection, sorting, and summary display. Duplicate directory
+/// trees and empty files and directories are reported in their own sections.
+///
+/// Progress is checkpointed as the scan runs; with `resume`, the scan
+/// continues from the checkpoint of an interrupted one.
+fn scan_and_display(args: &Args, dir: &Path, resume: bool) -> Findings {
     println!("Scanning {}...", dir.display());
 
-    let files = match scan_directory(dir) {
-        Ok(f) => f,
+    let path = args
+        .checkpoint
+        .clone()
+        .or_else(|| Checkpoint::default_path(dir));
+    let resumed = match &path {
+        Some(path) if resume => load_checkpoint(path, dir),
+        None if resume => {
+            eprintln!("Error: cannot determine checkpoint location; pass --checkpoint <FILE>");
+            process::exit(1);
+        }
+        _ => None,
+    };
+    let mut writer = path
+        .as_deref()
+        .and_then(|path| checkpoint_writer(path, dir, resumed.as_ref()));
+    let (walk, mut known) = match resumed {
+        Some(checkpoint) => (Some(checkpoint.walk), checkpoint.hashes),
+        None => (None, HashMap::new()),
+    };
+    for path in &args.import_manifest {
+        match manifest::import(path, dir) {
+            Ok(imported) => {
+                for (file, hash) in imported {
+                    known.entry(file).or_insert(hash);
+                }
+            }
+            Err(e) => {
+                eprintln!("Error reading manifest '{}': {}", path.display(), e);
+                process::exit(1);
+            }
+        }
+    }
+
+    let guard = CancelGuard::new();
+    let mut reporter = ProgressReporter::new();
+    let scanned = match walk {
+        Some(walk) => resume_scan(dir, walk, &mut |p| track(&mut reporter, &mut writer, p)),
+        None => scan_directory(dir, &mut |p| track(&mut reporter, &mut writer, p)),
+    };
+    let scan = match scanned {
+        Ok(s) => s,
         Err(e) => {
             eprintln!("Error scanning directory: {}", e);
             process::exit(1);
         }
     };
 
-    println!("Found {} files, analyzing for duplicates...", files.len());
+    let totals = DirTotals::from_files(dir, &scan.files);
+    let files = scan.files.len();
+    // The scan consumes its file list, so the index gets its own copy.
+    let indexed = if args.update_index {
+        scan.files.clone()
+    } else {
+        Vec::new()
+    };
+    // Only files that share their size are hashed to find duplicates, but an
+    // exported manifest lists every file, so that `verify` can check them all.
+    let unique = if args.export_manifest.is_some() {
+        unique_sizes(&scan.files)
+    } else {
+        Vec::new()
+    };
+    let mut hashes: BTreeMap<PathBuf, String> = scan
+        .files
+        .iter()
+        .filter_map(|f| Some((f.path.clone(), known.get(&f.path)?.clone())))
+        .collect();
+    if args.export_manifest.is_some() {
+        for file in &scan.empties.files {
+            hashes.insert(file.path.clone(), EMPTY_HASH.to_string());
+        }
+    }
+    let cancelled = cancel::requested();
+    if !cancelled {
+        reporter.finish();
+        println!("Found {} files, analyzing for duplicates...", files);
+    }
+    let mut record = |p: Progress| {
+        if let Progress::Hashed { path, hash } = p {
+            hashes.insert(path.to_path_buf(), hash.to_string());
+        }
+        track(&mut reporter, &mut writer, p)
+    };
+    let mut groups = if cancelled {
+        Vec::new()
+    } else {
+        find_duplicates(scan.files, &known, &mut record)
+    };
+    if !unique.is_empty() && !cancel::requested() {
+        group_by_hash(unique, &known, &mut record);
+    }
+    reporter.finish();
+    drop(guard);
+
+    if args.update_index {
+        update_index(args, dir, &indexed, &hashes, !cancel::requested());
+    }
+    if let Some(mut writer) = writer {
+        let result = if cancel::requested() {
+            writer.sync().map(|()| {
+                println!(
+                    "\nProgress saved to {}; rerun with --resume to continue the scan",
+                    writer.path().display()
+                )
+            })
+        } else {
+            writer.remove()
+        };
+        if let Err(e) = result {
+            eprintln!("Warning: cannot update checkpoint: {}", e);
+        }
+    }
+
+    let interrupted = cancel::requested();
+    if interrupted {
+        println!(
+            "\nScan interrupted: {} duplicate group(s) confirmed from the files hashed so far",
+            groups.len()
+        );
+        // A manifest of the files hashed so far would pass for the whole tree.
+        if args.export_manifest.is_some() {
+            eprintln!("Error: not writing an incomplete manifest of an interrupted scan");
+            process::exit(130);
+        }
+        if groups.is_empty() || !prompt_show_partial().unwrap_or(false) {
+            process::exit(130);
+        }
+    }
 
-    let groups = find_duplicates(files);
+    sort_groups(&mut groups, args.sort_groups, args.sort_paths);
     let stats = DuplicateStats::from_groups(&groups);
 
+    // Directory comparisons need every file, so they are skipped after an
+    // interrupted scan rather than reported from partial contents.
+    let (trees, overlaps) = if interrupted {
+        (Vec::new(), Vec::new())
+    } else {
+        let trees = find_duplicate_trees(dir, &groups, &scan.empties);
+        let overlaps = find_overlaps(dir, &totals, &groups, &trees, args.overlaps);
+        (trees, overlaps)
+    };
+
     display_summary(&groups, &stats);
+    display_trees(&trees);
+    display_overlaps(&overlaps);
+    display_empties(&scan.empties);
+
+    Findings {
+        groups,
+        trees,
+        empties: scan.empties,
+        hashes,
+    }
+}
+
+/// Returns the files whose size no other file has, which the duplicate
+/// search never reads.
+fn unique_sizes(files: &[FileInfo]) -> Vec<FileInfo> {
+    let mut counts: HashMap<u64, usize> = HashMap::new();
+    for file in files {
+        *counts.entry(file.size).or_default() += 1;
+    }
+    files
+        .iter()
+        .filter(|file| counts[&file.size] == 1)
+        .cloned()
+        .collect()
+}
+
+/// Records the files of a scan of `dir` in the hash index.
+///
+/// An interrupted scan only adds the files it got to. Failures are reported
+/// as warnings, since the scan results are still usable.
+fn update_index(
+    args: &Args,
+    dir: &Path,
+    files: &[FileInfo],
+    hashes: &BTreeMap<PathBuf, String>,
+    complete: bool,
+) {
+    let Some(path) = args.index.clone().or_else(HashIndex::default_path) else {
+        eprintln!("Warning: cannot determine index location; pass --index <FILE>");
+        return;
+    };
+    let result = HashIndex::open(&path).and_then(|mut index| {
+        index.update(dir, files, hashes, complete)?;
+        index.save()?;
+        Ok(index.len())
+    });
+    match result {
+        Ok(len) => println!("Index {} now holds {} file(s)", path.display(), len),
+        Err(e) => eprintln!("Warning: cannot update index '{}': {}", path.display(), e),
+    }
+}
+
+/// Reports a progress event and records it in the checkpoint.
+///
+/// Breaks once Ctrl-C was pressed. A checkpoint that cannot be written is
+/// reported once and then abandoned, since the scan itself can go on.
+fn track(
+    reporter: &mut ProgressReporter,
+    writer: &mut Option<CheckpointWriter>,
+    progress: Progress,
+) -> ControlFlow<()> {
+    reporter.update(progress);
+    if let Some(Err(e)) = writer.as_mut().map(|w| w.record(progress)) {
+        eprintln!("\nWarning: cannot write checkpoint: {}", e);
+        *writer = None;
+    }
+    cancel::check()
+}
+
+/// Reads the checkpoint at `path` to resume a scan of `dir`.
+///
+/// Exits if the checkpoint cannot be read or belongs to another directory.
+fn load_checkpoint(path: &Path, dir: &Path) -> Option<Checkpoint> {
+    match Checkpoint::load(path, dir) {
+        Ok(Some(checkpoint)) => {
+            println!("Resuming from checkpoint {}", path.display());
+            Some(checkpoint)
+        }
+        Ok(None) => {
+            println!("No checkpoint at {}; starting a new scan", path.display());
+            None
+        }
+        Err(e) => {
+            eprintln!("Error reading checkpoint '{}': {}", path.display(), e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Opens the checkpoint at `path`, continuing `resumed` if given.
+///
+/// Checkpointing is best effort, so failures are reported as warnings.
+fn checkpoint_writer(
+    path: &Path,
+    dir: &Path,
+    resumed: Option<&Checkpoint>,
+) -> Option<CheckpointWriter> {
+    let writer = match resumed {
+        Some(checkpoint) => CheckpointWriter::append(path, checkpoint, dir),
+        None => CheckpointWriter::create(path, dir),
+    };
+    writer
+        .map_err(|e| {
+            eprintln!(
+                "Warning: cannot write checkpoint '{}': {}",
+                path.display(),
+                e
+            )
+        })
+        .ok()
+}
+
+/// Reads the review session saved at `path`.
+///
+/// Exits if the session cannot be read or was saved for a directory other
+/// than `dir`, however either is spelled.
+fn open_session(path: &Path, dir: Option<&Path>) -> Option<Session> {
+    let canonical = |dir: &Path| fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+    match Session::open(path) {
+        Ok(Some(session)) if dir.is_some_and(|dir| canonical(dir) != canonical(&session.root)) => {
+            eprintln!(
+                "Error: {} is a session for {}, not {}",
+                path.display(),
+                session.root.display(),
+                dir.unwrap_or(&session.root).display()
+            );
+            process::exit(1);
+        }
+        Ok(Some(session)) => {
+            println!(
+                "Reopening session {} for {}: {} group(s), {} decision(s) so far",
+                path.display(),
+                session.root.display(),
+                session.groups.len(),
+                session.decision_count()
+            );
+            Some(session)
+        }
+        Ok(None) => None,
+        Err(e) => {
+            eprintln!("Error reading session '{}': {}", path.display(), e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Saves a session, reporting failures as warnings.
+fn save_session(session: &Session) {
+    if let Err(e) = session.save() {
+        eprintln!(
+            "Warning: cannot save session '{}': {}",
+            session.path().display(),
+            e
+        );
+    }
+}
+
+/// Revalidates the files of a session and displays its groups.
+///
+/// Only file groups are saved in a session, so duplicate directories and
+/// empty files are not reported until the next rescan.
+fn session_findings(session: &mut Session) -> Findings {
+    let dropped = session.revalidate();
+    if dropped > 0 {
+        println!(
+            "{} file(s) changed or disappeared since the scan and were dropped",
+            dropped
+        );
+    }
+    save_session(session);
+
+    let stats = DuplicateStats::from_groups(&session.groups);
+    display_summary(&session.groups, &stats);
+    Findings {
+        groups: session.groups.clone(),
+        trees: Vec::new(),
+        empties: Empties::default(),
+        hashes: session
+            .groups
+            .iter()
+            .flat_map(|g| g.paths.iter().map(|p| (p.clone(), g.hash.clone())))
+            .collect(),
+    }
+}
+
+/// Brings the findings up to date after files were removed.
+///
+/// In a session the saved groups are revalidated instead of scanning the
+/// directory again.
+fn refresh(args: &Args, dir: &Path, session: &mut Option<Session>) -> Findings {
+    match session {
+        Some(session) => session_findings(session),
+        None => scan_and_display(args, dir, false),
+    }
+}
+
+/// Scans the directory again, carrying session decisions over to the new
+/// results.
+fn rescan(args: &Args, dir: &Path, session: &mut Option<Session>) -> Findings {
+    let found = scan_and_display(args, dir, false);
+    if let Some(session) = session {
+        session.replace_groups(found.groups.clone());
+        save_session(session);
+    }
+    found
+}
+
+/// Fills in the options not given on the command line from the user's and
+/// the directory's configuration files, including the `--profile`.
+///
+/// Protected directories from the files are added to those given with
+/// `--protect`. Exits if a file is malformed or the profile is not defined.
+fn apply_config(args: &mut Args, matches: &ArgMatches, dir: &Path) {
+    let user = config::user_path();
+    let settings = match config::load(user.as_deref(), Some(dir), args.profile.as_deref()) {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("Error reading configuration: {}", e);
+            process::exit(1);
+        }
+    };
+    let given = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+
+    if let Some(keep) = settings.keep.filter(|_| !given("keep")) {
+        args.keep = keep;
+    }
+    args.protect.extend(settings.protect);
+    if let Some(order) = settings.sort_groups.filter(|_| !given("sort_groups")) {
+        args.sort_groups = order;
+    }
+    if let Some(order) = settings.sort_paths.filter(|_| !given("sort_paths")) {
+        args.sort_paths = order;
+    }
+    // `--permanent` and `--quarantine` exclude each other, so either one on
+    // the command line overrides both settings.
+    if !given("permanent") && !given("quarantine") {
+        if let Some(permanent) = settings.permanent {
+            args.permanent = permanent;
+        }
+        if let Some(quarantine) = settings.quarantine {
+            args.quarantine = Some(quarantine);
+        }
+    }
+    if let Some(dry_run) = settings.dry_run.filter(|_| !given("dry_run")) {
+        args.dry_run = dry_run;
+    }
+    if let Some(overlaps) = settings.overlaps.filter(|_| !given("overlaps")) {
+        args.overlaps = overlaps;
+    }
+    if let Some(mode) = settings
+        .prune_empty_dirs
+        .filter(|_| !given("prune_empty_dirs"))
+    {
+        args.prune_empty_dirs = Some(mode);
+    }
+    if let Some(verify) = settings.verify_hash.filter(|_| !given("verify_hash")) {
+        args.verify_hash = verify;
+    }
+    if let Some(journal) = settings.journal.filter(|_| !given("journal")) {
+        args.journal = Some(journal);
+    }
+}
+
+/// Opens the journal at `path`, or at the default location.
+///
+/// Exits if no path is given and the default location cannot be determined.
+fn open_journal(path: Option<PathBuf>) -> Journal {
+    match path.or_else(Journal::default_path) {
+        Some(path) => Journal::new(path),
+        None => {
+            eprintln!("Error: cannot determine journal location; pass --journal <FILE>");
+            process::exit(1);
+        }
+    }
+}
+
+/// Builds the keep policy from `--keep` rules and `--protect` directories.
+fn keep_policy(args: &Args) -> KeepPolicy {
+    KeepPolicy::new(args.keep.clone()).with_protected(&args.protect)
+}
+
+/// Builds the runner for removals requested from the interactive menu.
+///
+/// In a dry run no removal backend is set up at all, so not even the
+/// quarantine directory is created. Hashes taken from `--import-manifest`
+/// are only as good as the files' timestamps, so when any were imported
+/// both copies are hashed again before each removal.
+fn build_runner(args: &Args, directory: &Path) -> Runner {
+    let runner = if args.dry_run {
+        Runner::dry_run()
+    } else {
+        Runner::new(Executor {
+            mode: removal_mode(args, directory),
+            journal: open_journal(args.journal.clone()),
+        })
+    };
+
+    let runner = runner.with_hash_check(args.verify_hash || !args.import_manifest.is_empty());
+    match args.prune_empty_dirs {
+        Some(mode) => runner.with_pruner(Pruner::new(directory, mode)),
+        None => runner,
+    }
+}
+
+/// Chooses how files are removed from `--quarantine` and `--permanent`.
+fn removal_mode(args: &Args, directory: &Path) -> RemovalMode {
+    if let Some(dir) = &args.quarantine {
+        match Quarantine::new(dir, directory) {
+            Ok(q) => RemovalMode::Quarantine(q),
+            Err(e) => {
+                eprintln!("Error: cannot use quarantine '{}': {}", dir.display(), e);
+                process::exit(1);
+            }
+        }
+    } else if args.permanent {
+        RemovalMode::Permanent
+    } else {
+        RemovalMode::Trash
+    }
+}
+
+/// Writes a reviewable cleanup script for `groups` to `path`.
+fn write_script(args: &Args, directory: &Path, groups: &[DuplicateGroup], path: &Path) {
+    let target = args.quarantine.as_ref().map(|quarantine| MoveTarget {
+        quarantine: quarantine.clone(),
+        root: directory.to_path_buf(),
+    });
+    let plan = Plan::from_policy(groups, &keep_policy(args));
+
+    let written = script::generate(&plan, args.script_command, target.as_ref()).and_then(|bytes| {
+        fs::write(path, bytes)?;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+    });
+
+    match written {
+        Ok(()) => println!(
+            "\nWrote {} operation(s) to {}; review it, then run it with sh.",
+            plan.operations.len(),
+            path.display()
+        ),
+        Err(e) => {
+            eprintln!("Error writing script '{}': {}", path.display(), e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Checks a directory against a checksum manifest and exits.
+///
+/// Exits with status 1 if any file is missing, new, changed or unreadable,
+/// like `md5sum -c` does for failed checks.
+fn verify_tree(manifest_path: &Path, dir: &Path) -> io::Result<()> {
+    println!(
+        "Verifying {} against {}...",
+        dir.display(),
+        manifest_path.display()
+    );
+
+    let guard = CancelGuard::new();
+    let mut reporter = ProgressReporter::new();
+    let result = manifest::verify(manifest_path, dir, &mut |p| {
+        reporter.update(p);
+        cancel::check()
+    });
+    reporter.finish();
+    drop(guard);
+
+    let result = result?;
+    display_verification(&result);
+    if !result.is_clean() {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+/// Looks files up in the hash index and exits.
+///
+/// Exits with status 0 if any file already has a copy in the index and 1
+/// if none does, like `grep`. Exits with status 2 if the index cannot be
+/// used, or if nothing matched but some file could not be read.
+fn query_index(paths: &[PathBuf], index_path: Option<PathBuf>) -> ! {
+    let Some(index_path) = index_path.or_else(HashIndex::default_path) else {
+        eprintln!("Error: cannot determine index location; pass --index <FILE>");
+        process::exit(2);
+    };
+    let mut index = match HashIndex::open(&index_path) {
+        Ok(index) => index,
+        Err(e) => {
+            eprintln!("Error reading index '{}': {}", index_path.display(), e);
+            process::exit(2);
+        }
+    };
+
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let scan = match scan_directory(path, &mut |_| ControlFlow::Continue(())) {
+                Ok(scan) => scan,
+                Err(e) => {
+                    eprintln!("Error scanning '{}': {}", path.display(), e);
+                    process::exit(2);
+                }
+            };
+            files.extend(scan.files.into_iter().map(|f| f.path));
+        } else {
+            files.push(path.clone());
+        }
+    }
+
+    let result = index::query(&mut index, &files);
+    if let Err(e) = index.save() {
+        eprintln!(
+            "Warning: cannot update index '{}': {}",
+            index_path.display(),
+            e
+        );
+    }
+    display_query(&result, files.len());
+    process::exit(
+        match (result.found.is_empty(), result.unreadable.is_empty()) {
+            (false, _) => 0,
+            (true, true) => 1,
+            (true, false) => 2,
+        },
+    );
+}
+
+/// Lists the quarantined files older than `older_than` days and deletes
+/// them once confirmed, or right away with `yes`. A dry run stops after
+/// the listing.
+fn purge_quarantine(dir: &Path, older_than: u64, yes: bool, dry_run: bool) -> io::Result<()> {
+    let expired = quarantine::expired(dir, Duration::from_secs(older_than * 24 * 60 * 60))?;
+    if expired.is_empty() {
+        println!("No quarantined files older than {} day(s)", older_than);
+        return Ok(());
+    }
+
+    display_purge(&expired);
+    if dry_run {
+        println!("\nWould purge {} file(s)", expired.len());
+        return Ok(());
+    }
+    if !yes && !confirm_purge(expired.len())? {
+        println!("Purge cancelled.");
+        return Ok(());
+    }
+    let purged = quarantine::purge(dir, &expired)?;
+    println!("\nPurged {} file(s)", purged);
+    Ok(())
+}
 
-    groups
+/// Returns the journal set in the user's configuration file, including the
+/// `profile`.
+///
+/// Exits if the file is malformed or the profile is not defined.
+fn configured_journal(profile: Option<&str>) -> Option<PathBuf> {
+    let user = config::user_path();
+    match config::load(user.as_deref(), None, profile) {
+        Ok(settings) => settings.journal,
+        Err(e) => {
+            eprintln!("Error reading configuration: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Runs a subcommand and exits.
+fn run_command(command: Command) {
+    let result = match command {
+        Command::Quarantine {
+            action: QuarantineAction::Restore { dir },
+        } => quarantine::restore(&dir).map(|n| println!("\nRestored {} file(s)", n)),
+        Command::Quarantine {
+            action:
+                QuarantineAction::Purge {
+                    dir,
+                    older_than,
+                    yes,
+                    dry_run,
+                },
+        } => purge_quarantine(&dir, older_than, yes, dry_run),
+        Command::Undo {
+            journal,
+            profile,
+            last,
+            dry_run,
+        } => {
+            let journal = journal.or_else(|| configured_journal(profile.as_deref()));
+            journal::undo(&open_journal(journal), last, dry_run).map(|n| {
+                let verb = if dry_run { "Would restore" } else { "Restored" };
+                println!("\n{} {} file(s)", verb, n)
+            })
+        }
+        Command::Verify {
+            manifest,
+            directory,
+        } => verify_tree(&manifest, &directory),
+        Command::Query { paths, index } => query_index(&paths, index),
+    };
+
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
 }
 
 /// Application entry point.
@@ -61,26 +943,111 @@ fn scan_and_display(dir: &PathBuf) -> Vec<duplicates::DuplicateGroup> {
 /// Parses command-line arguments, performs initial scan, and runs the
 /// interactive main loop for duplicate management.
 fn main() {
-    let args = Args::parse();
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    if let Some(command) = args.command {
+        run_command(command);
+        return;
+    }
+
+    let mut session = args
+        .session
+        .as_deref()
+        .and_then(|path| open_session(path, args.directory.as_deref()));
 
-    if !args.directory.is_dir() {
+    // Guaranteed by clap: DIRECTORY is required unless a subcommand or a
+    // session is given. Sessions hold absolute paths, so that they can be
+    // reopened from any working directory, and scans for them use the same.
+    let directory = match (&args.directory, &session) {
+        (_, Some(session)) => session.root.clone(),
+        (Some(dir), None) if args.session.is_some() => {
+            std::path::absolute(dir).unwrap_or_else(|_| dir.clone())
+        }
+        (Some(dir), None) => dir.clone(),
+        (None, None) => {
+            eprintln!(
+                "Error: no session at '{}'; pass DIRECTORY to start one",
+                args.session.as_deref().unwrap_or(Path::new("")).display()
+            );
+            process::exit(1);
+        }
+    };
+    let directory = directory.as_path();
+
+    // A reopened session is not scanned, so there is nothing to index, and
+    // only the files in its groups have known hashes.
+    if session.is_some() && args.update_index {
+        eprintln!("Error: --update-index needs a scan and cannot be used when reopening a session");
+        process::exit(1);
+    }
+    if session.is_some() && args.export_manifest.is_some() {
         eprintln!(
-            "Error: '{}' is not a valid directory",
-            args.directory.display()
+            "Error: --export-manifest needs a scan and cannot be used when reopening a session"
         );
         process::exit(1);
     }
 
-    let mut groups = scan_and_display(&args.directory);
+    if !directory.is_dir() {
+        eprintln!("Error: '{}' is not a valid directory", directory.display());
+        process::exit(1);
+    }
+
+    apply_config(&mut args, &matches, directory);
+
+    let mut found = match &mut session {
+        Some(session) => session_findings(session),
+        None => scan_and_display(&args, directory, args.resume),
+    };
+    if let (Some(path), None) = (&args.session, &session) {
+        let started = Session::new(path, directory, found.groups.clone());
+        save_session(&started);
+        println!("Saving review decisions to {}", path.display());
+        session = Some(started);
+    }
+
+    if let Some(path) = &args.export_manifest {
+        match manifest::write(path, directory, &found.hashes) {
+            Ok(()) => println!(
+                "\nWrote {} hash(es) to {}",
+                found.hashes.len(),
+                path.display()
+            ),
+            Err(e) => {
+                eprintln!("Error writing manifest '{}': {}", path.display(), e);
+                process::exit(1);
+            }
+        }
+    }
+    if let Some(path) = &args.script {
+        write_script(&args, directory, &found.groups, path);
+        return;
+    }
+    if args.export_manifest.is_some() || args.update_index {
+        return;
+    }
+
+    let runner = build_runner(&args, directory);
+    let policy = keep_policy(&args);
 
     // Main interaction loop
     loop {
-        if groups.is_empty() {
+        if found.is_empty() {
             println!("\nNo duplicates to manage. Exiting.");
             break;
         }
 
-        let action = match show_main_menu(groups.len()) {
+        let decided: Vec<bool> = found
+            .groups
+            .iter()
+            .map(|g| session.as_ref().is_some_and(|s| s.is_decided(g)))
+            .collect();
+        let action = match show_main_menu(
+            &found.groups,
+            &decided,
+            found.trees.len(),
+            found.empties.len(),
+        ) {
             Ok(a) => a,
             Err(e) => {
                 eprintln!("Error: {}", e);
@@ -90,45 +1057,123 @@ fn main() {
 
         match action {
             Action::ReviewGroup(idx) => {
-                if let Some(group) = groups.get(idx) {
-                    match review_group(group, idx + 1) {
-                        Ok(to_delete) => {
+                if let Some(group) = found.groups.get(idx) {
+                    let earlier = session
+                        .as_ref()
+                        .filter(|s| s.is_decided(group))
+                        .map(|s| s.marks(group));
+                    match review_group(group, idx + 1, &policy, earlier.as_deref()) {
+                        Ok(None) => {}
+                        Ok(Some(to_delete)) => {
+                            if let Some(session) = &mut session {
+                                let marks: Vec<bool> = (0..group.paths.len())
+                                    .map(|i| to_delete.contains(&i))
+                                    .collect();
+                                session.decide(group, &marks);
+                                save_session(session);
+                            }
                             if !to_delete.is_empty() {
-                                if let Err(e) = delete_files(group, &to_delete) {
+                                if let Err(e) = delete_files(group, &to_delete, &policy, &runner) {
                                     eprintln!("Error deleting files: {}", e);
                                 }
-                                groups = scan_and_display(&args.directory);
+                                if !runner.is_dry_run() {
+                                    found = refresh(&args, directory, &mut session);
+                                }
                             }
                         }
                         Err(e) => eprintln!("Error: {}", e),
                     }
                 }
             }
+            Action::FullScreenReview => {
+                let earlier: Vec<Vec<bool>> = found
+                    .groups
+                    .iter()
+                    .map(|g| match &session {
+                        Some(session) => session.marks(g),
+                        None => vec![false; g.paths.len()],
+                    })
+                    .collect();
+                let mut marked = earlier.clone();
+                let reviewed = tui::review(&found.groups, &policy, &mut marked);
+
+                if let Some(session) = &mut session {
+                    for (group, (before, after)) in
+                        found.groups.iter().zip(earlier.iter().zip(&marked))
+                    {
+                        if before != after {
+                            session.decide(group, after);
+                        }
+                    }
+                    save_session(session);
+                }
+
+                match reviewed {
+                    Ok(Some(plan)) => {
+                        apply_plan(&plan, &runner);
+                        if !runner.is_dry_run() {
+                            found = refresh(&args, directory, &mut session);
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => eprintln!("Error: {}", e),
+                }
+            }
+            Action::DeleteFromDirectory => {
+                match delete_from_directory(&found.groups, &policy, &runner) {
+                    Ok(freed) => {
+                        if freed > 0 && !runner.is_dry_run() {
+                            found = refresh(&args, directory, &mut session);
+                        }
+                    }
+                    Err(e) => eprintln!("Error deleting files: {}", e),
+                }
+            }
             Action::DeleteAllDuplicates => {
-                if let Err(e) = delete_all_duplicates(&groups) {
+                if let Err(e) = delete_all_duplicates(&found.groups, &policy, &runner) {
                     eprintln!("Error deleting files: {}", e);
                 }
-                groups = scan_and_display(&args.directory);
-            }
-            Action::Rescan => {
-                groups = scan_and_display(&args.directory);
+                if !runner.is_dry_run() {
+                    found = refresh(&args, directory, &mut session);
+                }
             }
-            Action::Quit => {
-                match prompt_rescan() {
-                    Ok(true) => {
-                        groups = scan_and_display(&args.directory);
-                        if groups.is_empty() {
-                            println!("\nVerified: No duplicate files remain.");
-                            break;
+            Action::RemoveDuplicateDir(idx) => {
+                if let Some(tree) = found.trees.get(idx) {
+                    match remove_duplicate_dir(tree, idx + 1, &found.groups, &policy, &runner) {
+                        Ok(_) => {
+                            if !runner.is_dry_run() {
+                                found = rescan(&args, directory, &mut session);
+                            }
                         }
+                        Err(e) => eprintln!("Error: {}", e),
                     }
-                    Ok(false) => {
-                        println!("Goodbye!");
+                }
+            }
+            Action::DeleteEmpties => {
+                if let Err(e) = delete_empties(&found.empties, &policy, &runner) {
+                    eprintln!("Error deleting files: {}", e);
+                }
+                if !runner.is_dry_run() {
+                    found = rescan(&args, directory, &mut session);
+                }
+            }
+            Action::Rescan => {
+                found = rescan(&args, directory, &mut session);
+            }
+            Action::Quit => match prompt_rescan() {
+                Ok(true) => {
+                    found = rescan(&args, directory, &mut session);
+                    if found.groups.is_empty() {
+                        println!("\nVerified: No duplicate files remain.");
                         break;
                     }
-                    Err(_) => break,
                 }
-            }
+                Ok(false) => {
+                    println!("Goodbye!");
+                    break;
+                }
+                Err(_) => break,
+            },
         }
     }
 }
@@ -0,0 +1,424 @@
+//! Checksum manifests in the `md5sum` format.
+//!
+//! A manifest has one `<hash>  <path>` line per file, as written by `md5sum`
+//! and read back by `md5sum -c`. A `*` in place of the second space marks
+//! binary mode, which makes no difference on Unix. Paths containing a
+//! backslash or a newline are escaped as `\\` and `\n`, and their line
+//! starts with a backslash.
+//!
+//! Exported manifests list paths relative to the scanned directory, so they
+//! can be checked with `md5sum -c` from there. Relative paths in imported
+//! manifests are read the same way.
+//!
+//! A tree can also be verified against a manifest, which reports the files
+//! that went missing, appeared or changed content since it was written.
+
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::ops::ControlFlow;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::actions::EMPTY_HASH;
+use crate::scanner::{hash_file_with_progress, scan_directory, Progress};
+
+/// Differences between a tree and a manifest.
+#[derive(Debug, Default)]
+pub struct Verification {
+    /// Number of files whose content matches the manifest.
+    pub matched: usize,
+
+    /// Files listed in the manifest that no longer exist.
+    pub missing: Vec<PathBuf>,
+
+    /// Files in the tree that the manifest does not list.
+    pub new: Vec<PathBuf>,
+
+    /// Files whose content no longer matches the manifest.
+    pub changed: Vec<PathBuf>,
+
+    /// Files that could not be read, with the reason.
+    pub unreadable: Vec<(PathBuf, io::Error)>,
+
+    /// Set if the check was stopped before every file was compared.
+    pub interrupted: bool,
+}
+
+impl Verification {
+    /// Returns `true` if the tree matches the manifest exactly.
+    pub fn is_clean(&self) -> bool {
+        self.missing.is_empty()
+            && self.new.is_empty()
+            && self.changed.is_empty()
+            && self.unreadable.is_empty()
+            && !self.interrupted
+    }
+}
+
+/// Writes `hashes` to a manifest at `path`, replacing it.
+///
+/// Paths under `root` are written relative to it.
+pub fn write(path: &Path, root: &Path, hashes: &BTreeMap<PathBuf, String>) -> io::Result<()> {
+    let mut contents = Vec::new();
+    for (file, hash) in hashes {
+        let relative = file.strip_prefix(root).unwrap_or(file);
+        contents.extend(format_line(hash, relative));
+    }
+    fs::write(path, contents)
+}
+
+/// Reads the manifest at `path`, resolving relative paths against `root`.
+///
+/// # Returns
+///
+/// The hash of each listed file, or an error if the file is malformed or
+/// lists hashes other than MD5, which is what files are hashed with.
+pub fn read(path: &Path, root: &Path) -> io::Result<BTreeMap<PathBuf, String>> {
+    let contents = fs::read(path)?;
+    let mut entries = BTreeMap::new();
+
+    for (number, line) in contents.split(|&b| b == b'\n').enumerate() {
+        if line.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+        let (hash, file) = parse_line(line).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{} line {}: expected an MD5 hash, two spaces and a path",
+                    path.display(),
+                    number + 1
+                ),
+            )
+        })?;
+        entries.insert(root.join(file), hash);
+    }
+
+    Ok(entries)
+}
+
+/// Reads the manifest at `path` as a source of precomputed hashes.
+///
+/// Only entries that can be trusted are returned: files changed after the
+/// manifest was written, and files that no longer exist, are left out so
+/// that they are hashed again. Files are judged by their status change
+/// time, which unlike the modification time cannot be set back, and one
+/// changed in the same clock tick as the manifest counts as changed after.
+pub fn import(path: &Path, root: &Path) -> io::Result<HashMap<PathBuf, String>> {
+    let written = fs::metadata(path)?.modified()?;
+    let entries = read(path, root)?;
+
+    Ok(entries
+        .into_iter()
+        .filter(|(file, _)| {
+            fs::metadata(file)
+                .ok()
+                .and_then(|metadata| status_changed(&metadata))
+                .is_some_and(|changed| changed < written)
+        })
+        .collect())
+}
+
+/// Returns the status change time (ctime) of a file.
+fn status_changed(metadata: &fs::Metadata) -> Option<SystemTime> {
+    let secs = u64::try_from(metadata.ctime()).ok()?;
+    let nanos = u32::try_from(metadata.ctime_nsec()).ok()?;
+    SystemTime::UNIX_EPOCH.checked_add(Duration::new(secs, nanos))
+}
+
+/// Checks the files under `root` against the manifest at `path`.
+///
+/// Every listed file found in the tree is hashed and compared; the manifest
+/// itself is never reported as new.
+///
+/// # Arguments
+///
+/// * `path` - Manifest to verify against.
+/// * `root` - Directory the manifest's relative paths are relative to.
+/// * `progress` - Called as the tree is walked and files are hashed. If it
+///   breaks, the check stops and the result is marked as interrupted.
+///
+/// # Returns
+///
+/// The differences found, or an error if the manifest or the tree cannot
+/// be read.
+pub fn verify(
+    path: &Path,
+    root: &Path,
+    progress: &mut dyn FnMut(Progress) -> ControlFlow<()>,
+) -> io::Result<Verification> {
+    let mut expected = read(path, root)?;
+    let mut result = Verification::default();
+    let mut stopped = false;
+    let scan = scan_directory(root, &mut |p| {
+        let flow = progress(p);
+        stopped |= flow.is_break();
+        flow
+    })?;
+    // A partial walk would report most files as missing.
+    if stopped {
+        result.interrupted = true;
+        return Ok(result);
+    }
+
+    let manifest = fs::canonicalize(path)?;
+    let mut listed = Vec::new();
+    for file in scan.files.into_iter().chain(scan.empties.files) {
+        match expected.remove(&file.path) {
+            Some(hash) => listed.push((file, hash)),
+            None if fs::canonicalize(&file.path).is_ok_and(|p| p == manifest) => {}
+            None => result.new.push(file.path),
+        }
+    }
+    result.missing = expected.into_keys().collect();
+
+    let files_total = listed.len();
+    let bytes_total: u64 = listed.iter().map(|(file, _)| file.size).sum();
+    let mut bytes_before = 0;
+    for (i, (file, expected)) in listed.into_iter().enumerate() {
+        let hash = if file.size == 0 {
+            Ok(EMPTY_HASH.to_string())
+        } else {
+            let mut bytes_in_file = 0;
+            hash_file_with_progress(&file.path, &mut |chunk| {
+                bytes_in_file = (bytes_in_file + chunk).min(file.size);
+                progress(Progress::Hashing {
+                    files_done: i,
+                    files_total,
+                    bytes_done: bytes_before + bytes_in_file,
+                    bytes_total,
+                })
+            })
+        };
+        bytes_before += file.size;
+
+        match hash {
+            Ok(hash) if hash == expected => result.matched += 1,
+            Ok(_) => result.changed.push(file.path),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {
+                result.interrupted = true;
+                break;
+            }
+            Err(e) => result.unreadable.push((file.path, e)),
+        }
+
+        let flow = progress(Progress::Hashing {
+            files_done: i + 1,
+            files_total,
+            bytes_done: bytes_before,
+            bytes_total,
+        });
+        if flow.is_break() {
+            result.interrupted = i + 1 < files_total;
+            break;
+        }
+    }
+
+    result.new.sort();
+    result.changed.sort();
+    Ok(result)
+}
+
+/// Formats one manifest line, escaping the path if needed.
+fn format_line(hash: &str, path: &Path) -> Vec<u8> {
+    let bytes = path.as_os_str().as_bytes();
+    let escape = bytes.iter().any(|&b| b == b'\\' || b == b'\n');
+
+    let mut line = Vec::with_capacity(hash.len() + bytes.len() + 4);
+    if escape {
+        line.push(b'\\');
+    }
+    line.extend(hash.as_bytes());
+    line.extend(b"  ");
+    for &byte in bytes {
+        match byte {
+            b'\\' if escape => line.extend(b"\\\\"),
+            b'\n' if escape => line.extend(b"\\n"),
+            _ => line.push(byte),
+        }
+    }
+    line.push(b'\n');
+    line
+}
+
+/// Parses one manifest line into a lowercase hash and a path.
+fn parse_line(line: &[u8]) -> Option<(String, PathBuf)> {
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    let (escaped, line) = match line.strip_prefix(b"\\") {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+
+    let hash = line.get(..32)?;
+    if !hash.iter().all(u8::is_ascii_hexdigit) {
+        return None;
+    }
+    let file = match line.get(32..34)? {
+        b"  " | b" *" => &line[34..],
+        _ => return None,
+    };
+    if file.is_empty() {
+        return None;
+    }
+
+    let file = if escaped {
+        let mut unescaped = Vec::with_capacity(file.len());
+        let mut bytes = file.iter();
+        while let Some(&byte) = bytes.next() {
+            unescaped.push(match (byte, bytes.clone().next()) {
+                (b'\\', Some(b'\\')) => *bytes.next()?,
+                (b'\\', Some(b'n')) => {
+                    bytes.next();
+                    b'\n'
+                }
+                (b'\\', _) => return None,
+                (byte, _) => byte,
+            });
+        }
+        unescaped
+    } else {
+        file.to_vec()
+    };
+
+    Some((
+        String::from_utf8_lossy(hash).to_lowercase(),
+        PathBuf::from(OsStr::from_bytes(&file)),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use tempfile::TempDir;
+
+    const HASH: &str = "d41d8cd98f00b204e9800998ecf8427e";
+
+    #[test]
+    fn test_manifest_roundtrip() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("archive");
+        let hashes: BTreeMap<PathBuf, String> = [
+            (root.join("a b.txt"), HASH.to_string()),
+            (root.join("odd\\name\nhere"), HASH.replace('d', "e")),
+            (PathBuf::from("/elsewhere/c"), HASH.to_string()),
+        ]
+        .into_iter()
+        .collect();
+        let path = dir.path().join("MD5SUMS");
+
+        write(&path, &root, &hashes).unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(contents.contains(&format!("{}  a b.txt\n", HASH)));
+        assert!(contents.contains("\\e41e8c"));
+        assert!(contents.contains("odd\\\\name\\nhere\n"));
+        assert_eq!(read(&path, &root).unwrap(), hashes);
+    }
+
+    #[test]
+    fn test_reads_md5sum_variants() {
+        assert_eq!(
+            parse_line(format!("{} *bin/file\r", HASH.to_uppercase()).as_bytes()),
+            Some((HASH.to_string(), PathBuf::from("bin/file")))
+        );
+        // SHA-256 hashes cannot be compared with the MD5 hashes of a scan.
+        let sha256 = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+        assert_eq!(parse_line(format!("{}  file", sha256).as_bytes()), None);
+        assert_eq!(parse_line(format!("{} file", HASH).as_bytes()), None);
+        assert_eq!(
+            parse_line(format!("\\{}  bad\\escape", HASH).as_bytes()),
+            None
+        );
+    }
+
+    /// Writes `entries` to a manifest in `dir` and dates it a moment ago,
+    /// after the files already in `dir` last changed.
+    fn write_manifest(dir: &Path, entries: &str) -> PathBuf {
+        let path = dir.join("MD5SUMS");
+        fs::write(&path, entries).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_millis(25))
+            .unwrap();
+        path
+    }
+
+    #[test]
+    fn test_import_skips_files_changed_since() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("old"), "").unwrap();
+        fs::write(dir.path().join("new"), "").unwrap();
+        let path = write_manifest(
+            dir.path(),
+            &format!("{h}  old\n{h}  new\n{h}  missing\n", h = HASH),
+        );
+        fs::write(dir.path().join("new"), "").unwrap();
+
+        let imported = import(&path, dir.path()).unwrap();
+
+        assert_eq!(imported.len(), 1);
+        assert_eq!(
+            imported.get(&dir.path().join("old")),
+            Some(&HASH.to_string())
+        );
+    }
+
+    #[test]
+    fn test_import_skips_backdated_files() {
+        let dir = TempDir::new().unwrap();
+        let (a, b) = (dir.path().join("a"), dir.path().join("b"));
+        fs::write(&a, "aaaa").unwrap();
+        fs::write(&b, "aaaa").unwrap();
+        let hash = "74b87337454200d4d33f80c4663dc5e5";
+        let path = write_manifest(dir.path(), &format!("{h}  a\n{h}  b\n", h = hash));
+        fs::write(&b, "bbbb").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&b)
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(946_684_800))
+            .unwrap();
+
+        let imported = import(&path, dir.path()).unwrap();
+
+        assert_eq!(imported.get(&a), Some(&hash.to_string()));
+        assert!(!imported.contains_key(&b));
+    }
+
+    #[test]
+    fn test_verify_reports_differences() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        for (name, contents) in [("same", "same"), ("edited", "before"), ("gone", "x")] {
+            fs::write(root.join(name), contents).unwrap();
+        }
+        fs::write(root.join("empty"), "").unwrap();
+        let mut hashes = BTreeMap::new();
+        for name in ["same", "edited", "gone", "empty"] {
+            let path = root.join(name);
+            hashes.insert(path.clone(), crate::scanner::hash_file(&path).unwrap());
+        }
+        let path = root.join("MD5SUMS");
+        write(&path, root, &hashes).unwrap();
+
+        fs::write(root.join("edited"), "after!").unwrap();
+        fs::remove_file(root.join("gone")).unwrap();
+        fs::write(root.join("added"), "new").unwrap();
+
+        let result = verify(&path, root, &mut |_| ControlFlow::Continue(())).unwrap();
+
+        assert_eq!(result.matched, 2);
+        assert_eq!(result.changed, vec![root.join("edited")]);
+        assert_eq!(result.missing, vec![root.join("gone")]);
+        assert_eq!(result.new, vec![root.join("added")]);
+        assert!(!result.is_clean());
+    }
+}
@@ -0,0 +1,328 @@
+//! Partially overlapping directories.
+//!
+//! Backup folders are often "mostly the same" without being exact copies.
+//! Using the file-level duplicate groups, this module measures how much
+//! content each pair of directories shares, by bytes and by file count, so
+//! the most similar pairs can be listed for a merge-or-drop decision.
+//!
+//! A directory's content is everything below it. Shared content is counted
+//! as a multiset: three copies of a file in one directory and one in another
+//! share a single file. Similarity is the shared amount divided by the
+//! combined amount (a Jaccard index), so 100% means identical content.
+
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::duplicates::DuplicateGroup;
+use crate::scanner::FileInfo;
+use crate::trees::DirGroup;
+
+/// Groups with more copies than this do not suggest candidate pairs on
+/// their own. Files such as a common `LICENSE` would otherwise pair up
+/// every directory that contains one; they still count towards the scores
+/// of pairs suggested by other files.
+const MAX_PAIRING_COPIES: usize = 32;
+
+/// Number of files and bytes below each directory of a scan.
+#[derive(Debug, Clone, Default)]
+pub struct DirTotals {
+    totals: HashMap<PathBuf, (usize, u64)>,
+}
+
+impl DirTotals {
+    /// Adds up `files` into every directory between them and `root`.
+    ///
+    /// The root itself is not counted, since it holds everything.
+    pub fn from_files(root: &Path, files: &[FileInfo]) -> Self {
+        let mut totals: HashMap<PathBuf, (usize, u64)> = HashMap::new();
+        for file in files {
+            for dir in dirs_below(root, &file.path) {
+                let total = totals.entry(dir.to_path_buf()).or_default();
+                total.0 += 1;
+                total.1 += file.size;
+            }
+        }
+        Self { totals }
+    }
+
+    fn get(&self, dir: &Path) -> (usize, u64) {
+        self.totals.get(dir).copied().unwrap_or_default()
+    }
+}
+
+/// Content shared by two directories.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Overlap {
+    /// The two directories, in path order.
+    pub dirs: [PathBuf; 2],
+
+    /// Number of files below each directory.
+    pub files: [usize; 2],
+
+    /// Bytes below each directory.
+    pub bytes: [u64; 2],
+
+    /// Number of files present in both.
+    pub shared_files: usize,
+
+    /// Bytes present in both.
+    pub shared_bytes: u64,
+}
+
+impl Overlap {
+    /// Fraction of the pair's combined bytes that both directories share.
+    pub fn byte_similarity(&self) -> f64 {
+        jaccard(self.shared_bytes, self.bytes[0] + self.bytes[1])
+    }
+
+    /// Fraction of the pair's combined files that both directories share.
+    pub fn file_similarity(&self) -> f64 {
+        jaccard(
+            self.shared_files as u64,
+            (self.files[0] + self.files[1]) as u64,
+        )
+    }
+
+    /// Fraction of each directory's bytes that the other one also has.
+    pub fn byte_coverage(&self) -> [f64; 2] {
+        self.bytes.map(|total| fraction(self.shared_bytes, total))
+    }
+}
+
+fn jaccard(shared: u64, combined: u64) -> f64 {
+    fraction(shared, combined.saturating_sub(shared))
+}
+
+fn fraction(part: u64, whole: u64) -> f64 {
+    if whole == 0 {
+        0.0
+    } else {
+        part as f64 / whole as f64
+    }
+}
+
+/// Finds the pairs of directories that share the most content.
+///
+/// Pairs where one directory contains the other are ignored, as are pairs
+/// within copies of an identical directory tree, which is already reported
+/// on its own. Files whose copies all lie in one identical tree do not
+/// suggest pairs either, so the directories above the copies are not
+/// paired for sharing that tree alone.
+///
+/// # Arguments
+///
+/// * `root` - Directory that was scanned.
+/// * `totals` - File counts and sizes per directory from the same scan.
+/// * `groups` - File duplicate groups from the same scan.
+/// * `trees` - Identical directory trees from the same scan.
+/// * `limit` - Maximum number of pairs to return.
+///
+/// # Returns
+///
+/// Up to `limit` pairs, most similar by bytes first.
+pub fn find_overlaps(
+    root: &Path,
+    totals: &DirTotals,
+    groups: &[DuplicateGroup],
+    trees: &[DirGroup],
+    limit: usize,
+) -> Vec<Overlap> {
+    if limit == 0 {
+        return Vec::new();
+    }
+
+    let same_tree: HashMap<&Path, usize> = trees
+        .iter()
+        .enumerate()
+        .flat_map(|(i, tree)| tree.dirs.iter().map(move |dir| (dir.as_path(), i)))
+        .collect();
+    // Identical tree that `dir` is a copy of or lies inside, if any.
+    let tree_of = |dir: &Path| {
+        dir.ancestors()
+            .take_while(|d| *d != root && d.starts_with(root))
+            .find_map(|d| same_tree.get(d).copied())
+    };
+
+    // Copies of each group below every directory.
+    let mut copies: HashMap<&Path, HashMap<usize, usize>> = HashMap::new();
+    for (index, group) in groups.iter().enumerate() {
+        for path in &group.paths {
+            for dir in dirs_below(root, path) {
+                *copies.entry(dir).or_default().entry(index).or_default() += 1;
+            }
+        }
+    }
+
+    let mut candidates: HashSet<(&Path, &Path)> = HashSet::new();
+    for group in groups
+        .iter()
+        .filter(|g| g.paths.len() <= MAX_PAIRING_COPIES)
+    {
+        let tree = group.paths[0].parent().and_then(tree_of);
+        let in_one_tree = tree.is_some()
+            && group
+                .paths
+                .iter()
+                .all(|p| p.parent().and_then(tree_of) == tree);
+        if in_one_tree {
+            continue;
+        }
+
+        for (i, a) in group.paths.iter().enumerate() {
+            for b in &group.paths[i + 1..] {
+                for dir_a in dirs_below(root, a) {
+                    for dir_b in dirs_below(root, b) {
+                        let identical =
+                            tree_of(dir_a).is_some_and(|tree| tree_of(dir_b) == Some(tree));
+                        if !identical && !dir_a.starts_with(dir_b) && !dir_b.starts_with(dir_a) {
+                            candidates.insert(if dir_a < dir_b {
+                                (dir_a, dir_b)
+                            } else {
+                                (dir_b, dir_a)
+                            });
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let mut overlaps: Vec<Overlap> = candidates
+        .into_iter()
+        .map(|(a, b)| {
+            let (copies_a, copies_b) = (&copies[a], &copies[b]);
+            let (mut shared_files, mut shared_bytes) = (0, 0);
+            for (index, &count_a) in copies_a {
+                if let Some(&count_b) = copies_b.get(index) {
+                    let shared = count_a.min(count_b);
+                    shared_files += shared;
+                    shared_bytes += shared as u64 * groups[*index].size;
+                }
+            }
+
+            let (total_a, total_b) = (totals.get(a), totals.get(b));
+            Overlap {
+                dirs: [a.to_path_buf(), b.to_path_buf()],
+                files: [total_a.0, total_b.0],
+                bytes: [total_a.1, total_b.1],
+                shared_files,
+                shared_bytes,
+            }
+        })
+        .collect();
+
+    overlaps.sort_by(|x, y| {
+        y.byte_similarity()
+            .partial_cmp(&x.byte_similarity())
+            .unwrap_or(Ordering::Equal)
+            .then(y.shared_bytes.cmp(&x.shared_bytes))
+            .then_with(|| x.dirs.cmp(&y.dirs))
+    });
+    overlaps.truncate(limit);
+    overlaps
+}
+
+/// Directories containing `path`, from its parent up to but excluding `root`.
+fn dirs_below<'a>(root: &'a Path, path: &'a Path) -> impl Iterator<Item = &'a Path> {
+    path.ancestors()
+        .skip(1)
+        .take_while(move |dir| *dir != root && dir.starts_with(root))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::duplicates::find_duplicates;
+    use crate::scanner::{no_progress, scan_directory};
+    use crate::test_util::write;
+    use crate::trees::find_duplicate_trees;
+    use tempfile::TempDir;
+
+    fn overlaps(root: &Path, limit: usize) -> Vec<Overlap> {
+        let scan = scan_directory(root, &mut no_progress).unwrap();
+        let totals = DirTotals::from_files(root, &scan.files);
+        let groups = find_duplicates(scan.files, &HashMap::new(), &mut no_progress);
+        let trees = find_duplicate_trees(root, &groups, &scan.empties);
+        find_overlaps(root, &totals, &groups, &trees, limit)
+    }
+
+    #[test]
+    fn test_partial_backup_overlap() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        write(&root.join("backup1/a.txt"), b"aaaaaaaa");
+        write(&root.join("backup1/b.txt"), b"bb");
+        write(&root.join("backup2/a.txt"), b"aaaaaaaa");
+        write(&root.join("backup2/c.txt"), b"cc");
+
+        let found = overlaps(root, 10);
+
+        assert_eq!(found.len(), 1);
+        let pair = &found[0];
+        assert_eq!(pair.dirs, [root.join("backup1"), root.join("backup2")]);
+        assert_eq!(pair.shared_files, 1);
+        assert_eq!(pair.shared_bytes, 8);
+        assert_eq!(pair.bytes, [10, 10]);
+        // 8 shared of 12 distinct bytes, 1 shared of 3 distinct files.
+        assert!((pair.byte_similarity() - 8.0 / 12.0).abs() < 1e-9);
+        assert!((pair.file_similarity() - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(pair.byte_coverage(), [0.8, 0.8]);
+    }
+
+    #[test]
+    fn test_ignores_identical_trees_and_nested_pairs() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        write(&root.join("a/x.txt"), b"same");
+        write(&root.join("b/x.txt"), b"same");
+        write(&root.join("c/d/y.txt"), b"other");
+        write(&root.join("c/y.txt"), b"other");
+
+        assert!(overlaps(root, 10).is_empty());
+    }
+
+    #[test]
+    fn test_ignores_pairs_within_nested_identical_trees() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        for copy in ["a/project", "b/project-copy"] {
+            write(&root.join(copy).join("src/main.rs"), b"fn main() {}");
+            write(
+                &root.join(copy).join("src/lib/util.rs"),
+                b"pub fn util() {}",
+            );
+            write(&root.join(copy).join("README"), b"readme");
+        }
+        write(&root.join("a/notes.txt"), b"shared notes");
+        write(&root.join("b/notes.txt"), b"shared notes");
+        write(&root.join("b/other.txt"), b"only in b");
+
+        let found = overlaps(root, 10);
+
+        let pairs: Vec<_> = found.iter().map(|o| o.dirs.clone()).collect();
+        assert_eq!(pairs, vec![[root.join("a"), root.join("b")]]);
+    }
+
+    #[test]
+    fn test_most_similar_first_and_limit() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        write(&root.join("a/1"), b"one");
+        write(&root.join("a/2"), b"two");
+        write(&root.join("b/1"), b"one");
+        write(&root.join("b/2"), b"two");
+        write(&root.join("b/3"), b"three");
+        write(&root.join("c/1"), b"one");
+        write(&root.join("c/4"), b"a much larger file");
+
+        let found = overlaps(root, 10);
+        assert_eq!(found[0].dirs, [root.join("a"), root.join("b")]);
+        assert!(found
+            .windows(2)
+            .all(|w| w[0].byte_similarity() >= w[1].byte_similarity()));
+
+        assert_eq!(overlaps(root, 1).len(), 1);
+        assert!(overlaps(root, 0).is_empty());
+    }
+}
@@ -0,0 +1,333 @@
+//! Keep policies.
+//!
+//! A [`KeepPolicy`] decides which copy in a duplicate group survives. It is
+//! an ordered list of [`KeepRule`]s: the first rule decides, later rules only
+//! break its ties, and any remaining tie is broken by path order so that the
+//! choice never depends on scan order.
+//!
+//! Files under a protected directory override every rule: they are always
+//! preferred as the keeper and are never removed.
+
+use std::cmp::Ordering;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use regex::Regex;
+
+/// A single criterion for choosing which copy to keep.
+#[derive(Debug, Clone)]
+pub enum KeepRule {
+    /// Keep the copy with the oldest modification time.
+    Oldest,
+    /// Keep the copy with the newest modification time.
+    Newest,
+    /// Keep the copy with the shortest path.
+    ShortestPath,
+    /// Keep the copy with the longest path.
+    LongestPath,
+    /// Keep the copy with the fewest directory levels.
+    Shallowest,
+    /// Keep the copy with the most directory levels.
+    Deepest,
+    /// Keep a copy under this directory.
+    Prefer(PathBuf),
+    /// Keep a copy whose path matches this regular expression.
+    Match(Regex),
+}
+
+impl FromStr for KeepRule {
+    type Err = String;
+
+    /// Parses a rule such as `oldest`, `prefer:/srv/music` or `match:\.flac$`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(dir) = s.strip_prefix("prefer:") {
+            return std::path::absolute(dir)
+                .map(KeepRule::Prefer)
+                .map_err(|e| e.to_string());
+        }
+        if let Some(pattern) = s.strip_prefix("match:") {
+            return Regex::new(pattern)
+                .map(KeepRule::Match)
+                .map_err(|e| e.to_string());
+        }
+
+        match s {
+            "oldest" => Ok(KeepRule::Oldest),
+            "newest" => Ok(KeepRule::Newest),
+            "shortest-path" => Ok(KeepRule::ShortestPath),
+            "longest-path" => Ok(KeepRule::LongestPath),
+            "shallowest" => Ok(KeepRule::Shallowest),
+            "deepest" => Ok(KeepRule::Deepest),
+            _ => Err(format!(
+                "unknown keep rule '{}' (expected oldest, newest, shortest-path, \
+                 longest-path, shallowest, deepest, prefer:<DIR> or match:<REGEX>)",
+                s
+            )),
+        }
+    }
+}
+
+/// Facts about one candidate that the rules compare.
+struct Candidate<'a> {
+    path: &'a Path,
+    protected: bool,
+    absolute: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl KeepRule {
+    /// Orders two candidates so that the preferred keeper sorts first.
+    fn compare(&self, a: &Candidate, b: &Candidate) -> Ordering {
+        match self {
+            // Files whose mtime cannot be read sort last either way.
+            KeepRule::Oldest => by_mtime(a, b, |x, y| x.cmp(y)),
+            KeepRule::Newest => by_mtime(a, b, |x, y| y.cmp(x)),
+            KeepRule::ShortestPath => path_len(a.path).cmp(&path_len(b.path)),
+            KeepRule::LongestPath => path_len(b.path).cmp(&path_len(a.path)),
+            KeepRule::Shallowest => depth(a.path).cmp(&depth(b.path)),
+            KeepRule::Deepest => depth(b.path).cmp(&depth(a.path)),
+            KeepRule::Prefer(dir) => b
+                .absolute
+                .starts_with(dir)
+                .cmp(&a.absolute.starts_with(dir)),
+            KeepRule::Match(re) => {
+                let matches = |c: &Candidate| re.is_match(&c.path.to_string_lossy());
+                matches(b).cmp(&matches(a))
+            }
+        }
+    }
+
+    /// Returns `true` if this rule needs file modification times.
+    fn needs_mtime(&self) -> bool {
+        matches!(self, KeepRule::Oldest | KeepRule::Newest)
+    }
+}
+
+fn by_mtime(
+    a: &Candidate,
+    b: &Candidate,
+    order: impl Fn(&SystemTime, &SystemTime) -> Ordering,
+) -> Ordering {
+    match (&a.modified, &b.modified) {
+        (Some(x), Some(y)) => order(x, y),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+fn path_len(path: &Path) -> usize {
+    path.as_os_str().len()
+}
+
+fn depth(path: &Path) -> usize {
+    path.components().count()
+}
+
+/// An ordered list of keep rules plus the directories that are protected.
+#[derive(Debug, Clone, Default)]
+pub struct KeepPolicy {
+    /// Rules in priority order; later rules break ties of earlier ones.
+    pub rules: Vec<KeepRule>,
+
+    /// Directories whose files must never be removed.
+    ///
+    /// Stored both as given (made absolute) and with symlinks resolved, so
+    /// a protected file cannot slip through under an alternative path.
+    pub protected: Vec<PathBuf>,
+}
+
+impl KeepPolicy {
+    /// Creates a policy from rules in priority order.
+    pub fn new(rules: Vec<KeepRule>) -> Self {
+        Self {
+            rules,
+            protected: Vec::new(),
+        }
+    }
+
+    /// Adds directories whose files are always kept.
+    pub fn with_protected(mut self, dirs: &[PathBuf]) -> Self {
+        for dir in dirs {
+            if let Ok(absolute) = std::path::absolute(dir) {
+                self.protected.push(absolute);
+            }
+            if let Ok(canonical) = fs::canonicalize(dir) {
+                self.protected.push(canonical);
+            }
+        }
+        self
+    }
+
+    /// Returns `true` if `path` lies under a protected directory.
+    pub fn is_protected(&self, path: &Path) -> bool {
+        if self.protected.is_empty() {
+            return false;
+        }
+
+        let absolute = std::path::absolute(path).ok();
+        let canonical = fs::canonicalize(path).ok();
+
+        self.protected.iter().any(|dir| {
+            absolute.as_ref().is_some_and(|p| p.starts_with(dir))
+                || canonical.as_ref().is_some_and(|p| p.starts_with(dir))
+        })
+    }
+
+    /// Fails with `PermissionDenied` if `path` is protected.
+    pub fn ensure_unprotected(&self, path: &Path) -> io::Result<()> {
+        if self.is_protected(path) {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("refusing to remove protected file {}", path.display()),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns the index of the copy to keep, or `None` if `paths` is empty.
+    ///
+    /// A protected copy is always chosen over an unprotected one.
+    pub fn choose(&self, paths: &[PathBuf]) -> Option<usize> {
+        let needs_mtime = self.rules.iter().any(KeepRule::needs_mtime);
+        let candidates: Vec<Candidate> = paths
+            .iter()
+            .map(|path| Candidate {
+                path,
+                protected: self.is_protected(path),
+                absolute: std::path::absolute(path).unwrap_or_else(|_| path.clone()),
+                modified: needs_mtime
+                    .then(|| fs::metadata(path).and_then(|m| m.modified()).ok())
+                    .flatten(),
+            })
+            .collect();
+
+        (0..candidates.len()).min_by(|&a, &b| self.compare(&candidates[a], &candidates[b]))
+    }
+
+    fn compare(&self, a: &Candidate, b: &Candidate) -> Ordering {
+        b.protected
+            .cmp(&a.protected)
+            .then_with(|| {
+                self.rules
+                    .iter()
+                    .map(|rule| rule.compare(a, b))
+                    .find(|ordering| ordering.is_ne())
+                    .unwrap_or(Ordering::Equal)
+            })
+            .then_with(|| a.path.cmp(b.path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+    use tempfile::TempDir;
+
+    fn policy(specs: &[&str]) -> KeepPolicy {
+        KeepPolicy::new(specs.iter().map(|s| s.parse().unwrap()).collect())
+    }
+
+    fn paths(list: &[&str]) -> Vec<PathBuf> {
+        list.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn test_default_policy_uses_path_order() {
+        let candidates = paths(&["/b/x", "/a/x", "/c/x"]);
+
+        assert_eq!(KeepPolicy::default().choose(&candidates), Some(1));
+        assert_eq!(KeepPolicy::default().choose(&[]), None);
+    }
+
+    #[test]
+    fn test_path_length_and_depth_rules() {
+        let candidates = paths(&["/a/b/c/long-name.txt", "/a/s.txt", "/a/b/x.txt"]);
+
+        assert_eq!(policy(&["shortest-path"]).choose(&candidates), Some(1));
+        assert_eq!(policy(&["longest-path"]).choose(&candidates), Some(0));
+        assert_eq!(policy(&["shallowest"]).choose(&candidates), Some(1));
+        assert_eq!(policy(&["deepest"]).choose(&candidates), Some(0));
+    }
+
+    #[test]
+    fn test_prefer_and_match_rules() {
+        let candidates = paths(&["/dl/song.mp3", "/music/song.mp3", "/backup/song.mp3"]);
+
+        assert_eq!(policy(&["prefer:/music"]).choose(&candidates), Some(1));
+        assert_eq!(policy(&["match:^/backup/"]).choose(&candidates), Some(2));
+    }
+
+    #[test]
+    fn test_later_rules_break_ties() {
+        let candidates = paths(&["/music/b/song.mp3", "/music/a/song.mp3", "/dl/song.mp3"]);
+
+        // Both /music copies tie on the prefix rule; path order picks /music/a.
+        assert_eq!(policy(&["prefer:/music"]).choose(&candidates), Some(1));
+        assert_eq!(
+            policy(&["prefer:/music", "match:/b/"]).choose(&candidates),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn test_mtime_rules() {
+        let dir = TempDir::new().unwrap();
+        let candidates: Vec<PathBuf> = ["new", "old", "mid"]
+            .iter()
+            .map(|name| dir.path().join(name))
+            .collect();
+        for (path, secs) in candidates.iter().zip([3_000_000, 1_000_000, 2_000_000]) {
+            fs::write(path, b"x").unwrap();
+            fs::File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(UNIX_EPOCH + Duration::from_secs(secs))
+                .unwrap();
+        }
+
+        assert_eq!(policy(&["oldest"]).choose(&candidates), Some(1));
+        assert_eq!(policy(&["newest"]).choose(&candidates), Some(0));
+    }
+
+    #[test]
+    fn test_protected_copy_always_kept() {
+        let dir = TempDir::new().unwrap();
+        let canon = dir.path().join("canon");
+        fs::create_dir(&canon).unwrap();
+        let candidates = vec![dir.path().join("a/x"), canon.join("deeply/nested/x")];
+
+        let policy = policy(&["shallowest"]).with_protected(std::slice::from_ref(&canon));
+
+        assert_eq!(policy.choose(&candidates), Some(1));
+        assert!(policy.is_protected(&candidates[1]));
+        assert!(!policy.is_protected(&candidates[0]));
+    }
+
+    #[test]
+    fn test_protected_through_symlink() {
+        let dir = TempDir::new().unwrap();
+        let canon = dir.path().join("canon");
+        fs::create_dir(&canon).unwrap();
+        fs::write(canon.join("x"), b"x").unwrap();
+        std::os::unix::fs::symlink(&canon, dir.path().join("alias")).unwrap();
+
+        let policy = KeepPolicy::default().with_protected(&[canon]);
+
+        assert!(policy.is_protected(&dir.path().join("alias/x")));
+        assert!(policy
+            .ensure_unprotected(&dir.path().join("alias/x"))
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!("biggest".parse::<KeepRule>().is_err());
+        assert!("match:(".parse::<KeepRule>().is_err());
+    }
+}
@@ -0,0 +1,364 @@
+//! Previews of a file's metadata and contents.
+//!
+//! Identical contents do not make copies equally worth keeping: one may have
+//! the meaningful name, the right owner or the tags a photo manager stored in
+//! its extended attributes. A preview shows all of that together with the
+//! start of the file, as text lines or as a hex dump for binary files, and
+//! the file can be opened in `$PAGER` or the desktop's default application.
+
+use std::ffi::{CStr, CString, OsStr};
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::Path;
+use std::process::{Command, ExitStatus};
+
+use crate::duplicates::DuplicateStats;
+use crate::format::local_timestamp;
+
+/// Number of bytes read for the content preview.
+const PREVIEW_BYTES: usize = 4096;
+
+/// Number of text lines shown.
+const PREVIEW_LINES: usize = 20;
+
+/// Number of bytes shown in a hex dump.
+const HEXDUMP_BYTES: usize = 256;
+
+/// Longest extended attribute value shown, in bytes.
+const XATTR_VALUE_BYTES: usize = 64;
+
+/// Describes a file's metadata, one `name: value` line each.
+///
+/// Symbolic links are described themselves, not their targets.
+pub fn metadata(path: &Path) -> io::Result<Vec<String>> {
+    let meta = fs::symlink_metadata(path)?;
+    let mut lines = vec![
+        match DuplicateStats::format_bytes(meta.len()) {
+            size if meta.len() < 1024 => format!("Size:        {}", size),
+            size => format!("Size:        {} ({} bytes)", size, meta.len()),
+        },
+        format!(
+            "Modified:    {}",
+            meta.modified()
+                .map(local_timestamp)
+                .unwrap_or_else(|_| "unknown".to_string())
+        ),
+        format!(
+            "Owner:       {}:{}",
+            user_name(meta.uid()).unwrap_or_else(|| meta.uid().to_string()),
+            group_name(meta.gid()).unwrap_or_else(|| meta.gid().to_string())
+        ),
+        format!(
+            "Permissions: {} ({:04o})",
+            permission_string(meta.mode()),
+            meta.permissions().mode() & 0o7777
+        ),
+        format!("Links:       {}", meta.nlink()),
+    ];
+
+    match xattrs(path) {
+        Ok(attrs) if attrs.is_empty() => lines.push("Xattrs:      none".to_string()),
+        Ok(attrs) => {
+            lines.push("Xattrs:".to_string());
+            lines.extend(
+                attrs
+                    .iter()
+                    .map(|(name, value)| format!("  {} = {}", name, escape(value))),
+            );
+        }
+        Err(e) => lines.push(format!("Xattrs:      unavailable ({})", e)),
+    }
+
+    Ok(lines)
+}
+
+/// Shows the start of a file: its first lines if it looks like text, or a
+/// hex dump otherwise.
+pub fn contents(path: &Path) -> io::Result<String> {
+    let mut buffer = Vec::with_capacity(PREVIEW_BYTES);
+    File::open(path)?
+        .take(PREVIEW_BYTES as u64)
+        .read_to_end(&mut buffer)?;
+
+    Ok(match text_prefix(&buffer) {
+        Some(text) => first_lines(text, PREVIEW_LINES),
+        None => hexdump(&buffer[..buffer.len().min(HEXDUMP_BYTES)]),
+    })
+}
+
+/// Opens `path` in the pager named by `$PAGER`, or `less`.
+pub fn open_in_pager(path: &Path) -> io::Result<()> {
+    let pager = std::env::var("PAGER")
+        .ok()
+        .filter(|p| !p.trim().is_empty())
+        .unwrap_or_else(|| "less".to_string());
+
+    // `$PAGER` may carry options, such as `less -R`, so let the shell split it.
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", pager))
+        .arg("sh")
+        .arg(path)
+        .status()?;
+    check_status(&pager, status)
+}
+
+/// Opens `path` with the desktop's default application, using `xdg-open`.
+pub fn open_with_default(path: &Path) -> io::Result<()> {
+    let status = Command::new("xdg-open").arg(path).status()?;
+    check_status("xdg-open", status)
+}
+
+fn check_status(program: &str, status: ExitStatus) -> io::Result<()> {
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "{} exited with {}",
+            program, status
+        )))
+    }
+}
+
+/// Returns the text in `bytes` if it looks like UTF-8 text.
+///
+/// A character cut off at the end of the buffer is ignored.
+fn text_prefix(bytes: &[u8]) -> Option<&str> {
+    let text = match std::str::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&bytes[..e.valid_up_to()]).ok()?,
+        Err(_) => return None,
+    };
+    let binary = text
+        .chars()
+        .any(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t' | '\x0c'));
+    (!binary).then_some(text)
+}
+
+/// Returns the first `count` lines of `text`, noting if there are more.
+fn first_lines(text: &str, count: usize) -> String {
+    let mut preview = String::new();
+    let mut lines = text.lines();
+    for line in lines.by_ref().take(count) {
+        preview.push_str(line);
+        preview.push('\n');
+    }
+    if lines.next().is_some() {
+        preview.push_str("...\n");
+    }
+    preview
+}
+
+/// Formats bytes like `hexdump -C`: offset, 16 bytes in hex and as ASCII.
+fn hexdump(bytes: &[u8]) -> String {
+    let mut dump = String::new();
+    for (row, chunk) in bytes.chunks(16).enumerate() {
+        let _ = write!(dump, "{:08x}  ", row * 16);
+        for i in 0..16 {
+            match chunk.get(i) {
+                Some(byte) => {
+                    let _ = write!(dump, "{:02x} ", byte);
+                }
+                None => dump.push_str("   "),
+            }
+            if i == 7 {
+                dump.push(' ');
+            }
+        }
+        let ascii: String = chunk
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        let _ = writeln!(dump, " |{}|", ascii);
+    }
+    dump
+}
+
+/// Formats a mode like `ls -l`, such as `-rw-r--r--`.
+fn permission_string(mode: u32) -> String {
+    let kind = match mode & libc::S_IFMT {
+        libc::S_IFDIR => 'd',
+        libc::S_IFLNK => 'l',
+        libc::S_IFIFO => 'p',
+        libc::S_IFSOCK => 's',
+        libc::S_IFCHR => 'c',
+        libc::S_IFBLK => 'b',
+        _ => '-',
+    };
+
+    let mut string = String::from(kind);
+    for (shift, special, set, unset) in [
+        (6, 0o4000, 's', 'S'),
+        (3, 0o2000, 's', 'S'),
+        (0, 0o1000, 't', 'T'),
+    ] {
+        let bits = (mode >> shift) & 0o7;
+        string.push(if bits & 4 != 0 { 'r' } else { '-' });
+        string.push(if bits & 2 != 0 { 'w' } else { '-' });
+        string.push(match (bits & 1 != 0, mode & special != 0) {
+            (true, true) => set,
+            (false, true) => unset,
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    string
+}
+
+/// Shows an attribute value as text if it is printable, or in hex.
+fn escape(value: &[u8]) -> String {
+    let shown = &value[..value.len().min(XATTR_VALUE_BYTES)];
+    let more = if shown.len() < value.len() { "..." } else { "" };
+    match std::str::from_utf8(shown) {
+        Ok(text) if !text.chars().any(char::is_control) => format!("\"{}\"{}", text, more),
+        _ => {
+            let hex: String = shown.iter().map(|b| format!("{:02x}", b)).collect();
+            format!("0x{}{}", hex, more)
+        }
+    }
+}
+
+/// Lists a file's extended attributes and their values.
+fn xattrs(path: &Path) -> io::Result<Vec<(String, Vec<u8>)>> {
+    let path = CString::new(path.as_os_str().as_bytes()).map_err(io::Error::other)?;
+
+    // SAFETY: `path` is a valid C string, and each call is given the exact
+    // length of the buffer it may write to.
+    let names = read_sized(|buf, len| unsafe { libc::llistxattr(path.as_ptr(), buf, len) })?;
+    let mut attrs = Vec::new();
+    for name in names.split(|&b| b == 0).filter(|n| !n.is_empty()) {
+        let c_name = CString::new(name).map_err(io::Error::other)?;
+        // SAFETY: as above, with `c_name` a valid C string.
+        let value = read_sized(|buf, len| unsafe {
+            libc::lgetxattr(path.as_ptr(), c_name.as_ptr(), buf.cast(), len)
+        })?;
+        attrs.push((
+            OsStr::from_bytes(name).to_string_lossy().into_owned(),
+            value,
+        ));
+    }
+    Ok(attrs)
+}
+
+/// Calls `read` first for the size of a value, then to fill a buffer of
+/// that size.
+fn read_sized(read: impl Fn(*mut libc::c_char, usize) -> isize) -> io::Result<Vec<u8>> {
+    loop {
+        let len = read(std::ptr::null_mut(), 0);
+        if len < 0 {
+            let e = io::Error::last_os_error();
+            // Filesystems without xattr support simply have none.
+            return match e.raw_os_error() {
+                Some(libc::ENOTSUP) | Some(libc::ENODATA) => Ok(Vec::new()),
+                _ => Err(e),
+            };
+        }
+        let mut buffer = vec![0u8; len as usize];
+        let read_len = read(buffer.as_mut_ptr().cast(), buffer.len());
+        if read_len >= 0 {
+            buffer.truncate(read_len as usize);
+            return Ok(buffer);
+        }
+        // The value grew between the two calls; try again.
+        if io::Error::last_os_error().raw_os_error() != Some(libc::ERANGE) {
+            return Err(io::Error::last_os_error());
+        }
+    }
+}
+
+/// Looks up the name of a user.
+fn user_name(uid: u32) -> Option<String> {
+    let mut buffer = vec![0 as libc::c_char; 4096];
+    // SAFETY: all-zeroes is a valid `passwd`, and `getpwuid_r` only writes
+    // through the pointers it is given, within the stated buffer length.
+    unsafe {
+        let mut entry: libc::passwd = std::mem::zeroed();
+        let mut result = std::ptr::null_mut();
+        libc::getpwuid_r(
+            uid,
+            &mut entry,
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            &mut result,
+        );
+        (!result.is_null()).then(|| CStr::from_ptr(entry.pw_name).to_string_lossy().into_owned())
+    }
+}
+
+/// Looks up the name of a group.
+fn group_name(gid: u32) -> Option<String> {
+    let mut buffer = vec![0 as libc::c_char; 4096];
+    // SAFETY: as in `user_name`, for `group` and `getgrgid_r`.
+    unsafe {
+        let mut entry: libc::group = std::mem::zeroed();
+        let mut result = std::ptr::null_mut();
+        libc::getgrgid_r(
+            gid,
+            &mut entry,
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            &mut result,
+        );
+        (!result.is_null()).then(|| CStr::from_ptr(entry.gr_name).to_string_lossy().into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_text_files_show_first_lines() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("notes.txt");
+        let text: String = (1..=30).map(|i| format!("line {}\n", i)).collect();
+        fs::write(&path, text).unwrap();
+
+        let preview = contents(&path).unwrap();
+
+        assert!(preview.starts_with("line 1\nline 2\n"));
+        assert!(preview.contains("line 20\n"));
+        assert!(!preview.contains("line 21"));
+        assert!(preview.ends_with("...\n"));
+    }
+
+    #[test]
+    fn test_binary_files_show_hexdump() {
+        let dump = hexdump(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\x01");
+
+        assert_eq!(
+            dump,
+            "00000000  89 50 4e 47 0d 0a 1a 0a  00 00 00 0d 49 48 44 52  |.PNG........IHDR|\n\
+             00000010  00 01                                             |..|\n"
+        );
+        assert!(text_prefix(b"\x89PNG\r\n\x1a\n").is_none());
+        assert_eq!(
+            text_prefix("caf\u{e9}".as_bytes()[..4].as_ref()),
+            Some("caf")
+        );
+    }
+
+    #[test]
+    fn test_metadata_describes_permissions() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("script.sh");
+        fs::write(&path, "echo").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o754)).unwrap();
+
+        let lines = metadata(&path).unwrap();
+
+        assert!(lines.contains(&"Permissions: -rwxr-xr-- (0754)".to_string()));
+        assert!(lines.iter().any(|l| l.starts_with("Xattrs:")));
+        assert_eq!(permission_string(libc::S_IFDIR | 0o1777), "drwxrwxrwt");
+    }
+}
@@ -0,0 +1,255 @@
+//! Progress display for long scans.
+//!
+//! Turns the [`Progress`] events sent by the scanner into output on stderr:
+//! a single redrawn status line with a progress bar when stderr is a
+//! terminal, and a log line every few seconds otherwise, so redirected
+//! output stays readable.
+
+use std::io::{self, IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+use crate::duplicates::DuplicateStats;
+use crate::scanner::Progress;
+
+/// Minimum time between redraws of the terminal status line.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Minimum time between log lines when stderr is not a terminal.
+const LOG_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Time hashing must have run before throughput and ETA are estimated.
+const RATE_WARMUP: Duration = Duration::from_secs(1);
+
+/// Width of the progress bar in characters.
+const BAR_WIDTH: usize = 30;
+
+/// Renders scan progress to stderr.
+#[derive(Debug)]
+pub struct ProgressReporter {
+    /// Whether stderr is a terminal that can redraw a status line.
+    tty: bool,
+
+    /// When the current stage started, for throughput and ETA.
+    stage_started: Instant,
+
+    /// When output was last written, for throttling.
+    last_output: Option<Instant>,
+
+    /// Whether a status line is currently drawn and must be cleared.
+    line_drawn: bool,
+
+    /// Whether hashing has started, to reset the stage clock once.
+    hashing: bool,
+
+    /// Latest discovery count not shown yet because of throttling.
+    discovered: Option<usize>,
+}
+
+impl ProgressReporter {
+    /// Creates a reporter, drawing a bar only if stderr is a terminal.
+    pub fn new() -> Self {
+        Self {
+            tty: io::stderr().is_terminal(),
+            stage_started: Instant::now(),
+            last_output: None,
+            line_drawn: false,
+            hashing: false,
+            discovered: None,
+        }
+    }
+
+    /// Handles one progress event.
+    pub fn update(&mut self, progress: Progress) {
+        let line = match progress {
+            Progress::Visited(_) | Progress::Hashed { .. } => return,
+            Progress::Discovering { files } => {
+                if !self.due() {
+                    self.discovered = Some(files);
+                    return;
+                }
+                self.discovered = None;
+                discovering_line(files)
+            }
+            Progress::Candidates { files, bytes } => {
+                self.finish();
+                eprintln!(
+                    "{} file(s) ({}) share a size with another file and need hashing",
+                    files,
+                    DuplicateStats::format_bytes(bytes)
+                );
+                self.stage_started = Instant::now();
+                self.hashing = true;
+                return;
+            }
+            Progress::Hashing {
+                files_done,
+                files_total,
+                bytes_done,
+                bytes_total,
+            } => {
+                if !self.hashing {
+                    self.finish();
+                    self.stage_started = Instant::now();
+                    self.hashing = true;
+                }
+                let complete = files_done == files_total;
+                if !complete && !self.due() {
+                    return;
+                }
+                let elapsed = self.stage_started.elapsed();
+                let line = hashing_line(
+                    files_done,
+                    files_total,
+                    bytes_done,
+                    bytes_total,
+                    elapsed,
+                    self.tty,
+                );
+                if complete {
+                    self.show(&line);
+                    self.finish();
+                    return;
+                }
+                line
+            }
+        };
+
+        self.show(&line);
+    }
+
+    /// Ends the status line, if one is drawn, so normal output can follow.
+    ///
+    /// A discovery count held back by throttling is shown first, so the
+    /// final count always appears.
+    pub fn finish(&mut self) {
+        if let Some(files) = self.discovered.take() {
+            self.show(&discovering_line(files));
+        }
+        if self.line_drawn {
+            eprintln!();
+            self.line_drawn = false;
+        }
+        self.last_output = None;
+    }
+
+    /// Returns `true` if enough time has passed to write output again.
+    fn due(&self) -> bool {
+        let interval = if self.tty {
+            REDRAW_INTERVAL
+        } else {
+            LOG_INTERVAL
+        };
+        self.last_output
+            .is_none_or(|last| last.elapsed() >= interval)
+    }
+
+    fn show(&mut self, line: &str) {
+        if self.tty {
+            eprint!("\r\x1b[K{}", line);
+            let _ = io::stderr().flush();
+            self.line_drawn = true;
+        } else {
+            eprintln!("{}", line);
+        }
+        self.last_output = Some(Instant::now());
+    }
+}
+
+impl Default for ProgressReporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Formats the discovery status.
+fn discovering_line(files: usize) -> String {
+    format!("Discovering files: {} found", files)
+}
+
+/// Formats the hashing status, with a bar when `with_bar` is set.
+fn hashing_line(
+    files_done: usize,
+    files_total: usize,
+    bytes_done: u64,
+    bytes_total: u64,
+    elapsed: Duration,
+    with_bar: bool,
+) -> String {
+    let fraction = if bytes_total == 0 {
+        1.0
+    } else {
+        bytes_done as f64 / bytes_total as f64
+    };
+    let rate = bytes_done as f64 / elapsed.as_secs_f64();
+
+    let mut line = String::from("Hashing ");
+    if with_bar {
+        line.push_str(&bar(fraction));
+        line.push(' ');
+    }
+    line.push_str(&format!(
+        "{:5.1}% {}/{}, {}/{} files",
+        fraction * 100.0,
+        DuplicateStats::format_bytes(bytes_done),
+        DuplicateStats::format_bytes(bytes_total),
+        files_done,
+        files_total,
+    ));
+    if elapsed < RATE_WARMUP {
+        return line;
+    }
+    line.push_str(&format!(
+        ", {}/s",
+        DuplicateStats::format_bytes(rate as u64)
+    ));
+    if bytes_done < bytes_total && rate > 0.0 {
+        let remaining = (bytes_total - bytes_done) as f64 / rate;
+        line.push_str(&format!(", ETA {}", format_duration(remaining as u64)));
+    }
+    line
+}
+
+/// Draws a bar such as `[#######-------]` filled to `fraction`.
+fn bar(fraction: f64) -> String {
+    let filled = ((fraction.clamp(0.0, 1.0) * BAR_WIDTH as f64).round()) as usize;
+    format!("[{}{}]", "#".repeat(filled), "-".repeat(BAR_WIDTH - filled))
+}
+
+/// Formats seconds as `m:ss`, or `h:mm:ss` from one hour on.
+fn format_duration(secs: u64) -> String {
+    let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if h > 0 {
+        format!("{}:{:02}:{:02}", h, m, s)
+    } else {
+        format!("{}:{:02}", m, s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bar() {
+        assert_eq!(bar(0.0), format!("[{}]", "-".repeat(BAR_WIDTH)));
+        assert_eq!(bar(1.0), format!("[{}]", "#".repeat(BAR_WIDTH)));
+        assert_eq!(bar(0.5).matches('#').count(), BAR_WIDTH / 2);
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(5), "0:05");
+        assert_eq!(format_duration(125), "2:05");
+        assert_eq!(format_duration(3725), "1:02:05");
+    }
+
+    #[test]
+    fn test_hashing_line_reports_throughput_and_eta() {
+        let line = hashing_line(1, 4, 1024, 4096, Duration::from_secs(1), false);
+
+        assert_eq!(
+            line,
+            "Hashing  25.0% 1.00 KB/4.00 KB, 1/4 files, 1.00 KB/s, ETA 0:03"
+        );
+    }
+}
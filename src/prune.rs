@@ -0,0 +1,239 @@
+//! Removal of directories left empty after a cleanup.
+//!
+//! Deleting duplicates such as album art or readme files can leave behind
+//! whole trees of empty folders. A [`Pruner`] removes them after a plan has
+//! run. By default it only touches directories that the tool's own removals
+//! emptied, climbing from each removed file towards the scan root but never
+//! removing the root itself or anything outside it.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+
+/// Which empty directories are removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PruneMode {
+    /// Only directories emptied by this run's removals
+    Emptied,
+    /// Every empty directory under the scan root, including pre-existing ones
+    All,
+}
+
+/// Removes empty directories below a scan root.
+#[derive(Debug, Clone)]
+pub struct Pruner {
+    /// Directory that is never removed or climbed above.
+    pub root: PathBuf,
+
+    /// Which empty directories are removed.
+    pub mode: PruneMode,
+}
+
+impl Pruner {
+    /// Creates a pruner for the tree under `root`.
+    pub fn new(root: &Path, mode: PruneMode) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            mode,
+        }
+    }
+
+    /// Removes the directories left empty once `removed` files are gone.
+    ///
+    /// In a dry run nothing is touched: a directory counts as empty when all
+    /// of its entries would have been removed, so the result previews what a
+    /// real run would do.
+    ///
+    /// # Returns
+    ///
+    /// The directories removed (or that would be removed), deepest first.
+    pub fn prune(&self, removed: &[PathBuf], dry_run: bool) -> Vec<PathBuf> {
+        let mut gone: HashSet<PathBuf> = removed.iter().cloned().collect();
+        let mut pruned = Vec::new();
+
+        if self.mode == PruneMode::All {
+            let dirs = walkdir::WalkDir::new(&self.root)
+                .min_depth(1)
+                .contents_first(true)
+                .into_iter()
+                .filter_map(Result::ok)
+                .filter(|entry| entry.file_type().is_dir());
+
+            for entry in dirs {
+                self.try_remove(entry.path(), dry_run, &mut gone, &mut pruned);
+            }
+            return pruned;
+        }
+
+        for path in removed {
+            let mut dir = path.parent();
+            while let Some(current) = dir {
+                if !gone.contains(current)
+                    && !self.try_remove(current, dry_run, &mut gone, &mut pruned)
+                {
+                    break;
+                }
+                dir = current.parent();
+            }
+        }
+
+        pruned
+    }
+
+    /// Removes `dir` if it lies strictly below the root and is empty.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the directory was removed (or would be in a dry run).
+    fn try_remove(
+        &self,
+        dir: &Path,
+        dry_run: bool,
+        gone: &mut HashSet<PathBuf>,
+        pruned: &mut Vec<PathBuf>,
+    ) -> bool {
+        if dir == self.root || !dir.starts_with(&self.root) {
+            return false;
+        }
+
+        match is_empty(dir, gone) {
+            Ok(true) => {}
+            Ok(false) => return false,
+            Err(e) => {
+                eprintln!("  Error reading directory {}: {}", dir.display(), e);
+                return false;
+            }
+        }
+
+        if !dry_run {
+            if let Err(e) = fs::remove_dir(dir) {
+                eprintln!("  Error removing directory {}: {}", dir.display(), e);
+                return false;
+            }
+        }
+
+        gone.insert(dir.to_path_buf());
+        pruned.push(dir.to_path_buf());
+        true
+    }
+}
+
+/// Removes a tree that contains nothing but directories.
+///
+/// Directories are removed bottom-up with [`fs::remove_dir`], so anything
+/// that appeared in the tree since it was found empty makes this fail instead
+/// of being deleted.
+pub fn remove_empty_tree(dir: &Path) -> io::Result<()> {
+    for entry in walkdir::WalkDir::new(dir).contents_first(true) {
+        let entry = entry?;
+        if !entry.file_type().is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::DirectoryNotEmpty,
+                format!("{} is no longer empty", dir.display()),
+            ));
+        }
+    }
+
+    for entry in walkdir::WalkDir::new(dir).contents_first(true) {
+        fs::remove_dir(entry?.path())?;
+    }
+    Ok(())
+}
+
+/// Returns `true` if every entry of `dir` is in `gone`.
+fn is_empty(dir: &Path, gone: &HashSet<PathBuf>) -> io::Result<bool> {
+    for entry in fs::read_dir(dir)? {
+        if !gone.contains(&entry?.path()) {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Creates `root/` with a nested file, a sibling file and a pre-existing
+    /// empty directory, and returns the root.
+    fn setup(dir: &TempDir) -> PathBuf {
+        let root = dir.path().join("root");
+        fs::create_dir_all(root.join("album/art")).unwrap();
+        fs::create_dir_all(root.join("empty")).unwrap();
+        fs::write(root.join("album/art/cover.jpg"), b"x").unwrap();
+        fs::write(root.join("top.txt"), b"x").unwrap();
+        root
+    }
+
+    #[test]
+    fn test_prunes_only_emptied_dirs() {
+        let dir = TempDir::new().unwrap();
+        let root = setup(&dir);
+        let removed = vec![root.join("album/art/cover.jpg"), root.join("top.txt")];
+        for path in &removed {
+            fs::remove_file(path).unwrap();
+        }
+
+        let pruned = Pruner::new(&root, PruneMode::Emptied).prune(&removed, false);
+
+        assert_eq!(pruned, vec![root.join("album/art"), root.join("album")]);
+        assert!(!root.join("album").exists());
+        assert!(root.join("empty").exists());
+        assert!(root.exists());
+    }
+
+    #[test]
+    fn test_never_removes_root() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("root");
+        fs::create_dir(&root).unwrap();
+        fs::write(root.join("only.txt"), b"x").unwrap();
+        let removed = vec![root.join("only.txt")];
+        fs::remove_file(&removed[0]).unwrap();
+
+        for mode in [PruneMode::Emptied, PruneMode::All] {
+            assert!(Pruner::new(&root, mode).prune(&removed, false).is_empty());
+        }
+        assert!(root.exists());
+    }
+
+    #[test]
+    fn test_all_mode_includes_preexisting_empty_dirs() {
+        let dir = TempDir::new().unwrap();
+        let root = setup(&dir);
+
+        let pruned = Pruner::new(&root, PruneMode::All).prune(&[], false);
+
+        assert_eq!(pruned, vec![root.join("empty")]);
+        assert!(root.join("album/art/cover.jpg").exists());
+    }
+
+    #[test]
+    fn test_remove_empty_tree() {
+        let dir = TempDir::new().unwrap();
+        let root = setup(&dir);
+        fs::create_dir_all(root.join("empty/a/b")).unwrap();
+
+        remove_empty_tree(&root.join("empty")).unwrap();
+        assert!(!root.join("empty").exists());
+
+        assert!(remove_empty_tree(&root.join("album")).is_err());
+        assert!(root.join("album/art/cover.jpg").exists());
+    }
+
+    #[test]
+    fn test_dry_run_previews_without_removing() {
+        let dir = TempDir::new().unwrap();
+        let root = setup(&dir);
+        let removed = vec![root.join("album/art/cover.jpg")];
+
+        let pruned = Pruner::new(&root, PruneMode::Emptied).prune(&removed, true);
+
+        assert_eq!(pruned, vec![root.join("album/art"), root.join("album")]);
+        assert!(root.join("album/art/cover.jpg").exists());
+    }
+}
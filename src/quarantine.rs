@@ -0,0 +1,438 @@
+//! Quarantine directory support.
+//!
+//! Instead of deleting duplicates, files can be moved aside into a quarantine
+//! directory that mirrors their path relative to the scan root. Every move is
+//! appended to a manifest so the quarantine can later be restored in full or
+//! purged once a grace period has passed.
+//!
+//! The manifest is a tab-separated text file with one entry per line:
+//! `<unix seconds>\t<original path>\t<path inside quarantine>`, with both
+//! paths percent-encoded.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::format::{decode_path, encode_path, unix_now};
+
+/// File name of the manifest inside the quarantine directory.
+pub const MANIFEST_NAME: &str = "quarantine.manifest";
+
+/// A single quarantined file recorded in the manifest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManifestEntry {
+    /// When the file was quarantined, in seconds since the Unix epoch.
+    pub quarantined_at: u64,
+
+    /// Absolute path the file was moved from.
+    pub original: PathBuf,
+
+    /// Location of the file relative to the quarantine directory.
+    pub stored: PathBuf,
+}
+
+/// A quarantine directory that duplicates are moved into.
+#[derive(Debug, Clone)]
+pub struct Quarantine {
+    /// Directory holding quarantined files and the manifest.
+    pub dir: PathBuf,
+
+    /// Scan root that quarantined paths are made relative to.
+    pub root: PathBuf,
+}
+
+impl Quarantine {
+    /// Creates the quarantine directory if needed.
+    ///
+    /// Fails if the quarantine directory lies inside `root`, since quarantined
+    /// files would otherwise be found again as duplicates by the next scan.
+    pub fn new(dir: &Path, root: &Path) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+
+        if fs::canonicalize(dir)?.starts_with(fs::canonicalize(root)?) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "quarantine directory must be outside the scanned directory",
+            ));
+        }
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            root: root.to_path_buf(),
+        })
+    }
+
+    /// Moves a file into the quarantine and records it in the manifest.
+    ///
+    /// # Returns
+    ///
+    /// The new location of the file, or an IO error.
+    pub fn quarantine(&self, path: &Path) -> io::Result<PathBuf> {
+        let relative = path.strip_prefix(&self.root).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not under {}", path.display(), self.root.display()),
+            )
+        })?;
+
+        let stored = unique_path(&self.dir, relative);
+        let target = self.dir.join(&stored);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let entry = ManifestEntry {
+            quarantined_at: unix_now(),
+            original: std::path::absolute(path)?,
+            stored,
+        };
+
+        move_file(path, &target)?;
+        // A file in the quarantine without a manifest entry could never be
+        // restored, so the move is undone if the entry cannot be written.
+        if let Err(e) = append_entry(&self.dir, &entry) {
+            if let Err(back) = move_file(&target, path) {
+                eprintln!(
+                    "  Error moving {} back to {}: {}",
+                    target.display(),
+                    path.display(),
+                    back
+                );
+            }
+            return Err(e);
+        }
+
+        Ok(target)
+    }
+}
+
+/// Moves every quarantined file back to its original location.
+///
+/// Files whose original location is occupied again are left in the
+/// quarantine and stay in the manifest.
+///
+/// # Returns
+///
+/// The number of files restored, or an IO error if the manifest is unreadable.
+pub fn restore(dir: &Path) -> io::Result<usize> {
+    let mut remaining = Vec::new();
+    let mut restored = 0;
+
+    for entry in read_manifest(dir)? {
+        let source = dir.join(&entry.stored);
+
+        if fs::symlink_metadata(&entry.original).is_ok() {
+            eprintln!(
+                "  Skipping {}: original location already exists",
+                entry.original.display()
+            );
+            remaining.push(entry);
+            continue;
+        }
+
+        let moved = match entry.original.parent() {
+            Some(parent) => {
+                fs::create_dir_all(parent).and_then(|()| move_file(&source, &entry.original))
+            }
+            None => move_file(&source, &entry.original),
+        };
+
+        match moved {
+            Ok(()) => {
+                println!("  Restored: {}", entry.original.display());
+                restored += 1;
+            }
+            Err(e) => {
+                eprintln!("  Error restoring {}: {}", entry.original.display(), e);
+                remaining.push(entry);
+            }
+        }
+    }
+
+    write_manifest(dir, &remaining)?;
+    prune_empty_dirs(dir);
+
+    Ok(restored)
+}
+
+/// Returns the quarantined files older than `grace_period`.
+pub fn expired(dir: &Path, grace_period: Duration) -> io::Result<Vec<ManifestEntry>> {
+    let cutoff = unix_now().saturating_sub(grace_period.as_secs());
+    let mut entries = read_manifest(dir)?;
+    entries.retain(|entry| entry.quarantined_at <= cutoff);
+    Ok(entries)
+}
+
+/// Permanently deletes the quarantined files in `entries`, such as those
+/// returned by [`expired`].
+///
+/// # Returns
+///
+/// The number of files purged, or an IO error if the manifest is unreadable.
+pub fn purge(dir: &Path, entries: &[ManifestEntry]) -> io::Result<usize> {
+    let mut remaining = Vec::new();
+    let mut purged = 0;
+
+    for entry in read_manifest(dir)? {
+        if !entries.contains(&entry) {
+            remaining.push(entry);
+            continue;
+        }
+
+        let path = dir.join(&entry.stored);
+        match fs::remove_file(&path) {
+            Ok(()) => {
+                println!("  Purged: {}", entry.original.display());
+                purged += 1;
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => purged += 1,
+            Err(e) => {
+                eprintln!("  Error purging {}: {}", path.display(), e);
+                remaining.push(entry);
+            }
+        }
+    }
+
+    write_manifest(dir, &remaining)?;
+    prune_empty_dirs(dir);
+
+    Ok(purged)
+}
+
+/// Reads all entries from the manifest in `dir`.
+///
+/// A missing manifest is treated as an empty quarantine.
+pub fn read_manifest(dir: &Path) -> io::Result<Vec<ManifestEntry>> {
+    let contents = match fs::read_to_string(dir.join(MANIFEST_NAME)) {
+        Ok(c) => c,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    contents
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            parse_entry(line).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("malformed manifest line: {}", line),
+                )
+            })
+        })
+        .collect()
+}
+
+fn parse_entry(line: &str) -> Option<ManifestEntry> {
+    let mut fields = line.split('\t');
+    let entry = ManifestEntry {
+        quarantined_at: fields.next()?.parse().ok()?,
+        original: decode_path(fields.next()?)?,
+        stored: decode_path(fields.next()?)?,
+    };
+
+    fields.next().is_none().then_some(entry)
+}
+
+fn format_entry(entry: &ManifestEntry) -> String {
+    format!(
+        "{}\t{}\t{}\n",
+        entry.quarantined_at,
+        encode_path(&entry.original),
+        encode_path(&entry.stored)
+    )
+}
+
+fn append_entry(dir: &Path, entry: &ManifestEntry) -> io::Result<()> {
+    let mut manifest = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join(MANIFEST_NAME))?;
+
+    manifest.write_all(format_entry(entry).as_bytes())?;
+    manifest.sync_data()
+}
+
+/// Rewrites the manifest, removing it entirely when no entries remain.
+fn write_manifest(dir: &Path, entries: &[ManifestEntry]) -> io::Result<()> {
+    let path = dir.join(MANIFEST_NAME);
+
+    if entries.is_empty() {
+        return match fs::remove_file(&path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+    }
+
+    let contents: String = entries.iter().map(format_entry).collect();
+    let tmp = dir.join(format!("{}.tmp", MANIFEST_NAME));
+    fs::write(&tmp, contents)?;
+    fs::rename(tmp, path)
+}
+
+/// Returns `relative`, or a numbered variant of it, that is free inside `dir`.
+fn unique_path(dir: &Path, relative: &Path) -> PathBuf {
+    let mut candidate = relative.to_path_buf();
+    let mut counter = 2u32;
+
+    while fs::symlink_metadata(dir.join(&candidate)).is_ok() {
+        let mut name = relative.as_os_str().to_os_string();
+        name.push(format!(".{}", counter));
+        candidate = PathBuf::from(name);
+        counter += 1;
+    }
+
+    candidate
+}
+
+/// Moves a file, falling back to copy-and-delete across filesystems.
+///
+/// The copy keeps the original permissions and modification time.
+pub fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            let modified = fs::metadata(from)?.modified()?;
+            fs::copy(from, to)?;
+            fs::File::options()
+                .write(true)
+                .open(to)?
+                .set_modified(modified)?;
+            fs::remove_file(from)
+        }
+        result => result,
+    }
+}
+
+/// Removes empty subdirectories left behind inside the quarantine.
+fn prune_empty_dirs(dir: &Path) {
+    let subdirs = walkdir::WalkDir::new(dir)
+        .min_depth(1)
+        .contents_first(true)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_dir());
+
+    for entry in subdirs {
+        // Fails harmlessly for directories that still have contents.
+        let _ = fs::remove_dir(entry.path());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn setup() -> (TempDir, PathBuf, Quarantine) {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("music");
+        fs::create_dir_all(root.join("album")).unwrap();
+        fs::write(root.join("album/cover.jpg"), b"img").unwrap();
+
+        let quarantine = Quarantine::new(&dir.path().join("quarantine"), &root).unwrap();
+        (dir, root, quarantine)
+    }
+
+    #[test]
+    fn test_quarantine_mirrors_relative_path() {
+        let (_dir, root, quarantine) = setup();
+        let file = root.join("album/cover.jpg");
+
+        let target = quarantine.quarantine(&file).unwrap();
+
+        assert!(!file.exists());
+        assert_eq!(target, quarantine.dir.join("album/cover.jpg"));
+        assert_eq!(fs::read(&target).unwrap(), b"img");
+
+        let entries = read_manifest(&quarantine.dir).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].original, std::path::absolute(&file).unwrap());
+        assert_eq!(entries[0].stored, PathBuf::from("album/cover.jpg"));
+    }
+
+    #[test]
+    fn test_quarantine_inside_root_rejected() {
+        let dir = TempDir::new().unwrap();
+
+        assert!(Quarantine::new(&dir.path().join("q"), dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_restore_moves_files_back() {
+        let (_dir, root, quarantine) = setup();
+        let file = root.join("album/cover.jpg");
+        quarantine.quarantine(&file).unwrap();
+        fs::remove_dir(root.join("album")).unwrap();
+
+        let restored = restore(&quarantine.dir).unwrap();
+
+        assert_eq!(restored, 1);
+        assert_eq!(fs::read(&file).unwrap(), b"img");
+        assert!(!quarantine.dir.join(MANIFEST_NAME).exists());
+        assert!(!quarantine.dir.join("album").exists());
+    }
+
+    #[test]
+    fn test_restore_keeps_conflicting_entries() {
+        let (_dir, root, quarantine) = setup();
+        let file = root.join("album/cover.jpg");
+        quarantine.quarantine(&file).unwrap();
+        fs::write(&file, b"new").unwrap();
+
+        assert_eq!(restore(&quarantine.dir).unwrap(), 0);
+        assert_eq!(read_manifest(&quarantine.dir).unwrap().len(), 1);
+        assert_eq!(fs::read(&file).unwrap(), b"new");
+    }
+
+    #[test]
+    fn test_purge_respects_grace_period() {
+        let (_dir, root, quarantine) = setup();
+        fs::write(root.join("old.txt"), b"old").unwrap();
+        quarantine.quarantine(&root.join("old.txt")).unwrap();
+        quarantine
+            .quarantine(&root.join("album/cover.jpg"))
+            .unwrap();
+
+        // Backdate the first entry by two days.
+        let mut entries = read_manifest(&quarantine.dir).unwrap();
+        entries[0].quarantined_at -= 2 * 86400;
+        write_manifest(&quarantine.dir, &entries).unwrap();
+
+        let expired = expired(&quarantine.dir, Duration::from_secs(86400)).unwrap();
+        assert_eq!(expired.len(), 1);
+        let purged = purge(&quarantine.dir, &expired).unwrap();
+
+        assert_eq!(purged, 1);
+        assert!(!quarantine.dir.join("old.txt").exists());
+        assert!(quarantine.dir.join("album/cover.jpg").exists());
+        assert_eq!(read_manifest(&quarantine.dir).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_failed_manifest_write_moves_file_back() {
+        let (_dir, root, quarantine) = setup();
+        let file = root.join("album/cover.jpg");
+        // A directory in place of the manifest makes appending fail.
+        fs::create_dir(quarantine.dir.join(MANIFEST_NAME)).unwrap();
+
+        assert!(quarantine.quarantine(&file).is_err());
+        assert_eq!(fs::read(&file).unwrap(), b"img");
+        assert!(!quarantine.dir.join("album/cover.jpg").exists());
+    }
+
+    #[test]
+    fn test_unique_path_numbers_collisions() {
+        let (_dir, root, quarantine) = setup();
+        quarantine
+            .quarantine(&root.join("album/cover.jpg"))
+            .unwrap();
+        fs::write(root.join("album/cover.jpg"), b"again").unwrap();
+
+        let target = quarantine
+            .quarantine(&root.join("album/cover.jpg"))
+            .unwrap();
+
+        assert_eq!(target, quarantine.dir.join("album/cover.jpg.2"));
+    }
+}
@@ -3,10 +3,12 @@
 //! This module provides functionality for recursively scanning directories,
 //! collecting file metadata, and computing content hashes for duplicate detection.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::{self, File};
 use std::io::{self, Read};
+use std::ops::ControlFlow;
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 use md5::{Digest, Md5};
 use walkdir::WalkDir;
@@ -15,12 +17,190 @@ use walkdir::WalkDir;
 const HASH_BUFFER_SIZE: usize = 8192;
 
 /// Metadata about a file used for duplicate detection.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct FileInfo {
     /// Absolute path to the file.
     pub path: PathBuf,
     /// File size in bytes.
     pub size: u64,
+    /// Modification time when the file was scanned, if available.
+    pub modified: Option<SystemTime>,
+}
+
+/// Everything found by [`scan_directory`].
+#[derive(Debug, Clone, Default)]
+pub struct ScanResult {
+    /// Non-empty regular files, candidates for duplicate detection.
+    pub files: Vec<FileInfo>,
+
+    /// Empty files and directories, reported separately from duplicates.
+    pub empties: Empties,
+}
+
+/// Zero-byte files and empty directory trees found during a scan.
+#[derive(Debug, Clone, Default)]
+pub struct Empties {
+    /// Zero-byte regular files.
+    pub files: Vec<FileInfo>,
+
+    /// Topmost directories below the scan root that contain nothing but
+    /// other empty directories.
+    pub dirs: Vec<PathBuf>,
+}
+
+impl Empties {
+    /// Returns `true` if no empty files or directories were found.
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty() && self.dirs.is_empty()
+    }
+
+    /// Total number of empty files and directory trees.
+    pub fn len(&self) -> usize {
+        self.files.len() + self.dirs.len()
+    }
+}
+
+/// One entry handled by the directory walk.
+///
+/// Replaying the visits of an interrupted walk into a [`WalkState`] rebuilds
+/// what it had found, so it can be continued with [`resume_scan`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Visit {
+    /// A directory below the scan root.
+    Dir(PathBuf),
+    /// A regular file, empty or not.
+    File(FileInfo),
+    /// Any other entry, such as a symlink or a file whose metadata could not
+    /// be read.
+    Other(PathBuf),
+    /// A directory that could not be read.
+    Unreadable(PathBuf),
+}
+
+impl Visit {
+    /// Path of the visited entry.
+    pub fn path(&self) -> &Path {
+        match self {
+            Visit::Dir(path) | Visit::Other(path) | Visit::Unreadable(path) => path,
+            Visit::File(info) => &info.path,
+        }
+    }
+}
+
+/// What a directory walk has found so far.
+#[derive(Debug, Clone, Default)]
+pub struct WalkState {
+    /// Regular files, including empty ones.
+    files: Vec<FileInfo>,
+    /// Directories below the root, in walk order.
+    dirs: Vec<PathBuf>,
+    /// Directories with something other than directories somewhere below them.
+    occupied: HashSet<PathBuf>,
+    /// Last entry visited. The walk is done with everything before it.
+    position: Option<PathBuf>,
+}
+
+impl WalkState {
+    /// Adds a visited entry to the state.
+    pub fn record(&mut self, visit: Visit) {
+        self.position = Some(visit.path().to_path_buf());
+        match visit {
+            Visit::Dir(path) => self.dirs.push(path),
+            Visit::File(info) => {
+                if let Some(parent) = info.path.parent() {
+                    mark_occupied(&mut self.occupied, parent);
+                }
+                self.files.push(info);
+            }
+            Visit::Other(path) => {
+                if let Some(parent) = path.parent() {
+                    mark_occupied(&mut self.occupied, parent);
+                }
+            }
+            Visit::Unreadable(path) => mark_occupied(&mut self.occupied, &path),
+        }
+    }
+
+    /// Returns `true` if the walk already handled `path` and everything
+    /// below it.
+    fn is_done(&self, path: &Path) -> bool {
+        // Entries are visited in path order, so everything up to the
+        // position is done except the directories still being walked.
+        self.position
+            .as_deref()
+            .is_some_and(|position| path <= position && !position.starts_with(path))
+    }
+
+    /// Returns `true` if `path` was already visited.
+    fn is_visited(&self, path: &Path) -> bool {
+        self.position
+            .as_deref()
+            .is_some_and(|position| path <= position)
+    }
+
+    /// Splits the files found into a [`ScanResult`].
+    ///
+    /// Empty directories are only reported for a `complete` walk, since
+    /// anything not yet visited may still fill them.
+    fn into_result(self, complete: bool) -> ScanResult {
+        let mut result = ScanResult::default();
+        for info in self.files {
+            if info.size == 0 {
+                result.empties.files.push(info);
+            } else {
+                result.files.push(info);
+            }
+        }
+        if !complete {
+            return result;
+        }
+
+        // Directories come in pre-order, so a parent is seen before its children.
+        for path in self.dirs {
+            let inside_reported = result
+                .empties
+                .dirs
+                .last()
+                .is_some_and(|d| path.starts_with(d));
+            if !self.occupied.contains(&path) && !inside_reported {
+                result.empties.dirs.push(path);
+            }
+        }
+        result
+    }
+}
+
+/// Progress of a scan, reported through a callback.
+///
+/// Events are sent often, up to once per hashed chunk, so callbacks that
+/// render them should throttle their output. A callback returning
+/// [`ControlFlow::Break`] stops the scan early, keeping what was found so far.
+///
+/// [`Progress::Visited`] and [`Progress::Hashed`] carry everything needed to
+/// resume an interrupted scan, so a callback can checkpoint them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Progress<'a> {
+    /// The directory walk has found `files` regular files so far.
+    Discovering { files: usize },
+    /// The directory walk handled an entry.
+    Visited(&'a Visit),
+    /// Size grouping left `files` candidates totalling `bytes` to hash.
+    Candidates { files: usize, bytes: u64 },
+    /// Candidates are being hashed.
+    Hashing {
+        files_done: usize,
+        files_total: usize,
+        bytes_done: u64,
+        bytes_total: u64,
+    },
+    /// A candidate was hashed.
+    Hashed { path: &'a Path, hash: &'a str },
+}
+
+/// A progress callback that never stops the scan.
+#[cfg(test)]
+pub fn no_progress(_: Progress) -> ControlFlow<()> {
+    ControlFlow::Continue(())
 }
 
 /// Computes the MD5 hash of a file using chunked reading.
@@ -43,6 +223,17 @@ pub struct FileInfo {
 /// println!("MD5: {}", hash);
 /// ```
 pub fn hash_file(path: &Path) -> io::Result<String> {
+    hash_file_with_progress(path, &mut |_| ControlFlow::Continue(()))
+}
+
+/// Computes the MD5 hash of a file, calling `on_chunk` with the number of
+/// bytes in each chunk read.
+///
+/// Fails with [`io::ErrorKind::Interrupted`] if `on_chunk` breaks.
+pub fn hash_file_with_progress(
+    path: &Path,
+    on_chunk: &mut dyn FnMut(u64) -> ControlFlow<()>,
+) -> io::Result<String> {
     let mut file = File::open(path)?;
     let mut hasher = Md5::new();
     let mut buffer = [0u8; HASH_BUFFER_SIZE];
@@ -53,6 +244,12 @@ pub fn hash_file(path: &Path) -> io::Result<String> {
             break;
         }
         hasher.update(&buffer[..bytes_read]);
+        if on_chunk(bytes_read as u64).is_break() {
+            return Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                "hashing cancelled",
+            ));
+        }
     }
 
     Ok(format!("{:x}", hasher.finalize()))
@@ -62,29 +259,91 @@ pub fn hash_file(path: &Path) -> io::Result<String> {
 ///
 /// Walks the directory tree, collecting path and size for each regular file.
 /// Symbolic links are followed. Files that cannot be accessed are silently skipped.
+/// Directory entries are visited in file name order, so the result is the same
+/// on every run.
+///
+/// Zero-byte files and empty directory trees are collected separately in
+/// [`ScanResult::empties`] rather than being treated as duplicates of each
+/// other. A directory that cannot be read is never reported as empty.
 ///
 /// # Arguments
 ///
 /// * `dir` - Root directory to scan.
+/// * `progress` - Called with [`Progress::Discovering`] for every regular
+///   file found and [`Progress::Visited`] for every entry handled. If it
+///   breaks, the walk stops and the files found so far are returned, without
+///   any empty directories since none can be confirmed.
 ///
 /// # Returns
 ///
-/// A vector of [`FileInfo`] for all accessible files, or an IO error.
-pub fn scan_directory(dir: &Path) -> io::Result<Vec<FileInfo>> {
-    let mut files = Vec::new();
-
-    for entry in WalkDir::new(dir).into_iter().filter_map(Result::ok) {
-        if entry.file_type().is_file() {
-            if let Ok(metadata) = fs::metadata(entry.path()) {
-                files.push(FileInfo {
-                    path: entry.path().to_path_buf(),
-                    size: metadata.len(),
-                });
+/// A [`ScanResult`] for all accessible files, or an IO error.
+pub fn scan_directory(
+    dir: &Path,
+    progress: &mut dyn FnMut(Progress) -> ControlFlow<()>,
+) -> io::Result<ScanResult> {
+    resume_scan(dir, WalkState::default(), progress)
+}
+
+/// Continues an interrupted [`scan_directory`] from the state it reached.
+///
+/// Directories the walk already finished are skipped without being read
+/// again, so only the remainder of the tree is walked.
+pub fn resume_scan(
+    dir: &Path,
+    mut state: WalkState,
+    progress: &mut dyn FnMut(Progress) -> ControlFlow<()>,
+) -> io::Result<ScanResult> {
+    let mut discovered = state.files.len();
+    let mut walk = WalkDir::new(dir).sort_by_file_name().into_iter();
+
+    while let Some(entry) = walk.next() {
+        let visit = match entry {
+            Ok(entry) if state.is_done(entry.path()) => {
+                if entry.file_type().is_dir() {
+                    walk.skip_current_dir();
+                }
+                continue;
             }
+            Ok(entry) if entry.depth() == 0 || state.is_visited(entry.path()) => continue,
+            Ok(entry) if entry.file_type().is_dir() => Visit::Dir(entry.into_path()),
+            Ok(entry) if entry.file_type().is_file() => {
+                discovered += 1;
+                if progress(Progress::Discovering { files: discovered }).is_break() {
+                    return Ok(state.into_result(false));
+                }
+                match fs::metadata(entry.path()) {
+                    Ok(metadata) => Visit::File(FileInfo {
+                        path: entry.into_path(),
+                        size: metadata.len(),
+                        modified: metadata.modified().ok(),
+                    }),
+                    Err(_) => Visit::Other(entry.into_path()),
+                }
+            }
+            Ok(entry) => Visit::Other(entry.into_path()),
+            Err(e) => match e.path() {
+                Some(path) => Visit::Unreadable(path.to_path_buf()),
+                None => continue,
+            },
+        };
+
+        let flow = progress(Progress::Visited(&visit));
+        state.record(visit);
+        if flow.is_break() {
+            return Ok(state.into_result(false));
         }
     }
 
-    Ok(files)
+    Ok(state.into_result(true))
+}
+
+/// Marks `dir` and all of its ancestors as holding something.
+fn mark_occupied(occupied: &mut HashSet<PathBuf>, dir: &Path) {
+    for ancestor in dir.ancestors() {
+        if !occupied.insert(ancestor.to_path_buf()) {
+            break;
+        }
+    }
 }
 
 /// Groups files by size, filtering to only potential duplicates.
@@ -99,9 +358,10 @@ pub fn scan_directory(dir: &Path) -> io::Result<Vec<FileInfo>> {
 /// # Returns
 ///
 /// A map from file size to files of that size, containing only sizes
-/// with two or more files.
-pub fn group_by_size(files: Vec<FileInfo>) -> HashMap<u64, Vec<FileInfo>> {
-    let mut size_groups: HashMap<u64, Vec<FileInfo>> = HashMap::new();
+/// with two or more files. Sizes are in ascending order and files keep
+/// their input order.
+pub fn group_by_size(files: Vec<FileInfo>) -> BTreeMap<u64, Vec<FileInfo>> {
+    let mut size_groups: BTreeMap<u64, Vec<FileInfo>> = BTreeMap::new();
 
     for file in files {
         size_groups.entry(file.size).or_default().push(file);
@@ -116,21 +376,80 @@ pub fn group_by_size(files: Vec<FileInfo>) -> HashMap<u64, Vec<FileInfo>> {
 /// Computes MD5 hashes for each file and groups them. Files that fail
 /// to hash (e.g., permission denied) are silently skipped.
 ///
+/// A file that fails to hash or changed size still counts as its scanned
+/// size, so the reported progress always ends at the total.
+///
 /// # Arguments
 ///
 /// * `files` - Vector of files to hash and group.
+/// * `known` - Hashes computed earlier, such as before an interrupted scan,
+///   which are used instead of reading those files again.
+/// * `progress` - Called with [`Progress::Hashing`] as bytes are read and
+///   [`Progress::Hashed`] for every new hash. If it breaks, hashing stops and
+///   only the files fully hashed so far are grouped.
 ///
 /// # Returns
 ///
 /// A map from hash to files with that hash, containing only hashes
-/// with two or more files (actual duplicates).
-pub fn group_by_hash(files: Vec<FileInfo>) -> HashMap<String, Vec<FileInfo>> {
-    let mut hash_groups: HashMap<String, Vec<FileInfo>> = HashMap::new();
+/// with two or more files (actual duplicates). Hashes are in ascending
+/// order and files keep their input order.
+pub fn group_by_hash(
+    files: Vec<FileInfo>,
+    known: &HashMap<PathBuf, String>,
+    progress: &mut dyn FnMut(Progress) -> ControlFlow<()>,
+) -> BTreeMap<String, Vec<FileInfo>> {
+    let mut hash_groups: BTreeMap<String, Vec<FileInfo>> = BTreeMap::new();
+    let files_total = files.len();
+    let bytes_total: u64 = files.iter().map(|f| f.size).sum();
+    let mut bytes_before = 0;
+
+    for (i, file) in files.into_iter().enumerate() {
+        let hash = match known.get(&file.path) {
+            Some(hash) => Ok(hash.clone()),
+            None => {
+                let mut bytes_in_file = 0;
+                let mut stopped = false;
+                let hash = hash_file_with_progress(&file.path, &mut |chunk| {
+                    bytes_in_file = (bytes_in_file + chunk).min(file.size);
+                    let flow = progress(Progress::Hashing {
+                        files_done: i,
+                        files_total,
+                        bytes_done: bytes_before + bytes_in_file,
+                        bytes_total,
+                    });
+                    stopped = flow.is_break();
+                    flow
+                });
+                if stopped {
+                    break;
+                }
+                if let Ok(hash) = &hash {
+                    let flow = progress(Progress::Hashed {
+                        path: &file.path,
+                        hash,
+                    });
+                    if flow.is_break() {
+                        break;
+                    }
+                }
+                hash
+            }
+        };
 
-    for file in files {
-        if let Ok(hash) = hash_file(&file.path) {
+        bytes_before += file.size;
+        if let Ok(hash) = hash {
             hash_groups.entry(hash).or_default().push(file);
         }
+
+        let flow = progress(Progress::Hashing {
+            files_done: i + 1,
+            files_total,
+            bytes_done: bytes_before,
+            bytes_total,
+        });
+        if flow.is_break() {
+            break;
+        }
     }
 
     hash_groups.retain(|_, group| group.len() > 1);
@@ -202,25 +521,135 @@ mod tests {
             .write_all(b"test")
             .unwrap();
 
-        let files = scan_directory(dir.path()).unwrap();
+        let files = scan_directory(dir.path(), &mut no_progress).unwrap().files;
 
         assert_eq!(files.len(), 3);
     }
 
+    #[test]
+    fn test_scan_directory_reports_empties() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("a/empty/nested")).unwrap();
+        fs::create_dir_all(dir.path().join("b/full")).unwrap();
+        fs::create_dir_all(dir.path().join("b/hollow")).unwrap();
+        File::create(dir.path().join("b/full/zero.txt")).unwrap();
+        File::create(dir.path().join("c.txt"))
+            .unwrap()
+            .write_all(b"data")
+            .unwrap();
+
+        let result = scan_directory(dir.path(), &mut no_progress).unwrap();
+
+        assert_eq!(result.files.len(), 1);
+        assert_eq!(result.empties.files.len(), 1);
+        assert_eq!(
+            result.empties.files[0].path,
+            dir.path().join("b/full/zero.txt")
+        );
+        assert_eq!(
+            result.empties.dirs,
+            vec![dir.path().join("a"), dir.path().join("b/hollow")]
+        );
+    }
+
+    #[test]
+    fn test_hashing_progress_reaches_total() {
+        let dir = TempDir::new().unwrap();
+        let files: Vec<FileInfo> = [&b"abc"[..], &[7u8; 20000][..]]
+            .iter()
+            .enumerate()
+            .map(|(i, contents)| {
+                let path = dir.path().join(i.to_string());
+                fs::write(&path, contents).unwrap();
+                FileInfo {
+                    path,
+                    size: contents.len() as u64,
+                    modified: None,
+                }
+            })
+            .collect();
+
+        let mut events = Vec::new();
+        group_by_hash(files, &HashMap::new(), &mut |p| {
+            if let Progress::Hashing {
+                files_done,
+                files_total,
+                bytes_done,
+                bytes_total,
+            } = p
+            {
+                events.push((files_done, files_total, bytes_done, bytes_total));
+            }
+            ControlFlow::Continue(())
+        });
+
+        // Each file reports its chunks and then its completion.
+        assert!(events.len() > 4);
+        assert_eq!(events.last(), Some(&(2, 2, 20003, 20003)));
+    }
+
+    #[test]
+    fn test_group_by_hash_stops_when_cancelled() {
+        let dir = TempDir::new().unwrap();
+        let files: Vec<FileInfo> = ["a", "b", "c", "d"]
+            .iter()
+            .map(|name| {
+                let path = dir.path().join(name);
+                fs::write(&path, b"same").unwrap();
+                FileInfo {
+                    path,
+                    size: 4,
+                    modified: None,
+                }
+            })
+            .collect();
+
+        // Stop once two files are fully hashed.
+        let groups = group_by_hash(files, &HashMap::new(), &mut |p| match p {
+            Progress::Hashing { files_done: 2, .. } => ControlFlow::Break(()),
+            _ => ControlFlow::Continue(()),
+        });
+
+        assert_eq!(groups.len(), 1);
+        let group = groups.values().next().unwrap();
+        assert_eq!(group.len(), 2);
+    }
+
+    #[test]
+    fn test_scan_directory_stops_when_cancelled() {
+        let dir = TempDir::new().unwrap();
+        for name in ["a", "b", "c"] {
+            fs::write(dir.path().join(name), b"data").unwrap();
+        }
+        fs::create_dir(dir.path().join("z")).unwrap();
+
+        let result = scan_directory(dir.path(), &mut |p| match p {
+            Progress::Discovering { files: 2 } => ControlFlow::Break(()),
+            _ => ControlFlow::Continue(()),
+        })
+        .unwrap();
+
+        assert_eq!(result.files.len(), 1);
+        assert!(result.empties.dirs.is_empty());
+    }
+
     #[test]
     fn test_group_by_size() {
         let files = vec![
             FileInfo {
                 path: PathBuf::from("a.txt"),
                 size: 100,
+                modified: None,
             },
             FileInfo {
                 path: PathBuf::from("b.txt"),
                 size: 100,
+                modified: None,
             },
             FileInfo {
                 path: PathBuf::from("c.txt"),
                 size: 200,
+                modified: None,
             },
         ];
 
@@ -257,18 +686,21 @@ mod tests {
             FileInfo {
                 path: file1,
                 size: 12,
+                modified: None,
             },
             FileInfo {
                 path: file2,
                 size: 12,
+                modified: None,
             },
             FileInfo {
                 path: file3,
                 size: 9,
+                modified: None,
             },
         ];
 
-        let groups = group_by_hash(files);
+        let groups = group_by_hash(files, &HashMap::new(), &mut no_progress);
 
         // Only files with "same content" are duplicates
         assert_eq!(groups.len(), 1);
@@ -0,0 +1,369 @@
+//! Shell script generation.
+//!
+//! Turns a removal [`Plan`] into a POSIX shell script that can be reviewed
+//! and signed off before anything is changed. Before each operation the
+//! script re-checks the size and MD5 hash of both the file being removed and
+//! the copy being kept, and aborts if either changed since the scan.
+//!
+//! Paths are written as single-quoted shell words, which may contain any
+//! byte except NUL, so arbitrary file names survive the round trip.
+
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use clap::ValueEnum;
+
+use crate::actions::Plan;
+use crate::duplicates::DuplicateStats;
+use crate::format::{encode_path, local_timestamp};
+use crate::quarantine::MANIFEST_NAME;
+
+/// Command used in the script to get rid of each duplicate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ScriptCommand {
+    /// Delete duplicates with `rm`
+    Rm,
+    /// Replace duplicates with hard links to the kept copy using `ln`
+    Ln,
+    /// Move duplicates into the quarantine directory with `mv`
+    Mv,
+}
+
+/// Where `mv` operations move files to.
+#[derive(Debug, Clone)]
+pub struct MoveTarget {
+    /// Quarantine directory receiving the files.
+    pub quarantine: PathBuf,
+
+    /// Scan root that moved paths are made relative to.
+    pub root: PathBuf,
+}
+
+/// Shell functions shared by every generated script.
+const PRELUDE: &str = r#"set -eu
+
+fail() {
+    printf 'find_duplicates: %s; aborting\n' "$1" >&2
+    exit 1
+}
+
+hash_of() {
+    if command -v md5sum >/dev/null 2>&1; then
+        md5sum < "$1" | cut -d ' ' -f 1
+    elif command -v md5 >/dev/null 2>&1; then
+        md5 < "$1"
+    else
+        openssl md5 -r < "$1" | cut -d ' ' -f 1
+    fi
+}
+
+# verify PATH SIZE HASH
+verify() {
+    [ -f "$1" ] || fail "$1 no longer exists"
+    [ "$(wc -c < "$1" | tr -d ' ')" = "$2" ] || fail "$1 changed size"
+    [ "$(hash_of "$1")" = "$3" ] || fail "$1 changed content"
+}
+"#;
+
+/// Generates a shell script that carries out `plan`.
+///
+/// # Arguments
+///
+/// * `plan` - Operations to perform.
+/// * `command` - Whether duplicates are removed, hard-linked or moved.
+/// * `target` - Quarantine destination, required for [`ScriptCommand::Mv`].
+///
+/// # Returns
+///
+/// The script as raw bytes, since paths need not be valid UTF-8, or an
+/// error if `mv` was requested without a quarantine directory or `ln` for
+/// a file without a kept copy to link to.
+pub fn generate(
+    plan: &Plan,
+    command: ScriptCommand,
+    target: Option<&MoveTarget>,
+) -> io::Result<Vec<u8>> {
+    if command == ScriptCommand::Mv && target.is_none() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "mv scripts need a quarantine directory",
+        ));
+    }
+    if command == ScriptCommand::Ln {
+        if let Some(op) = plan.operations.iter().find(|op| op.keeper.is_none()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "cannot hard-link {}: no copy of it is kept",
+                    op.victim.display()
+                ),
+            ));
+        }
+    }
+
+    let mut script = Vec::new();
+    writeln!(script, "#!/bin/sh")?;
+    writeln!(
+        script,
+        "# Generated by find_duplicates on {}",
+        local_timestamp(SystemTime::now())
+    )?;
+    writeln!(
+        script,
+        "# {} operation(s), freeing {}",
+        plan.operations.len(),
+        DuplicateStats::format_bytes(plan.total_bytes())
+    )?;
+    writeln!(script)?;
+    script.extend_from_slice(PRELUDE.as_bytes());
+
+    for op in &plan.operations {
+        let victim = std::path::absolute(&op.victim)?;
+        let keeper = op.keeper.as_deref().map(std::path::absolute).transpose()?;
+
+        writeln!(script)?;
+        match &keeper {
+            Some(keeper) => {
+                script.extend_from_slice(b"# keep ");
+                script.extend(quote(keeper));
+                script.push(b'\n');
+                write_verify(&mut script, keeper, op.size, &op.hash)?;
+            }
+            None => writeln!(script, "# no copy of this file is kept")?,
+        }
+        write_verify(&mut script, &victim, op.size, &op.hash)?;
+
+        match (command, &keeper, target) {
+            (ScriptCommand::Ln, Some(keeper), _) => {
+                write_words(&mut script, &[b"ln", b"-f", b"--"], &[keeper, &victim])
+            }
+            (ScriptCommand::Mv, _, Some(target)) => write_move(&mut script, &victim, target)?,
+            _ => write_words(&mut script, &[b"rm", b"-f", b"--"], &[&victim]),
+        }
+    }
+
+    Ok(script)
+}
+
+/// Quotes bytes as a single shell word.
+///
+/// Everything is wrapped in single quotes, inside which no character is
+/// special; embedded single quotes are written as `'\''`.
+pub fn quote(path: &Path) -> Vec<u8> {
+    let mut quoted = vec![b'\''];
+
+    for &byte in path.as_os_str().as_bytes() {
+        if byte == b'\'' {
+            quoted.extend_from_slice(b"'\\''");
+        } else {
+            quoted.push(byte);
+        }
+    }
+
+    quoted.push(b'\'');
+    quoted
+}
+
+fn write_verify(script: &mut Vec<u8>, path: &Path, size: u64, hash: &str) -> io::Result<()> {
+    script.extend_from_slice(b"verify ");
+    script.extend(quote(path));
+    writeln!(script, " {} {}", size, hash)
+}
+
+fn write_words(script: &mut Vec<u8>, words: &[&[u8]], paths: &[&Path]) {
+    for word in words {
+        script.extend_from_slice(word);
+        script.push(b' ');
+    }
+    for (i, path) in paths.iter().enumerate() {
+        if i > 0 {
+            script.push(b' ');
+        }
+        script.extend(quote(path));
+    }
+    script.push(b'\n');
+}
+
+/// Writes the commands moving `victim` into the quarantine and appending it
+/// to the quarantine manifest, so `quarantine restore` can undo the move.
+fn write_move(script: &mut Vec<u8>, victim: &Path, target: &MoveTarget) -> io::Result<()> {
+    let root = std::path::absolute(&target.root)?;
+    let quarantine = std::path::absolute(&target.quarantine)?;
+    let relative = victim.strip_prefix(&root).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not under {}", victim.display(), root.display()),
+        )
+    })?;
+    let destination = quarantine.join(relative);
+
+    script.extend_from_slice(b"[ ! -e ");
+    script.extend(quote(&destination));
+    script.extend_from_slice(b" ] || fail ");
+    script.extend(quote(&destination));
+    script.extend_from_slice(b"' already exists'\n");
+
+    if let Some(parent) = destination.parent() {
+        write_words(script, &[b"mkdir", b"-p", b"--"], &[parent]);
+    }
+    write_words(script, &[b"mv", b"--"], &[victim, &destination]);
+
+    script.extend_from_slice(b"printf '%s\\t%s\\t%s\\n' \"$(date +%s)\" ");
+    script.extend(quote(Path::new(&encode_path(victim))));
+    script.push(b' ');
+    script.extend(quote(Path::new(&encode_path(relative))));
+    script.extend_from_slice(b" >> ");
+    script.extend(quote(&quarantine.join(MANIFEST_NAME)));
+    script.push(b'\n');
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::duplicates::DuplicateGroup;
+    use crate::quarantine::read_manifest;
+    use crate::scanner::hash_file;
+    use std::collections::HashMap;
+    use std::ffi::OsStr;
+    use std::fs;
+    use std::process::Command;
+    use tempfile::TempDir;
+
+    /// Creates two identical files under `root/` and a plan removing the second.
+    fn setup(names: [&str; 2]) -> (TempDir, [PathBuf; 2], Plan) {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("root")).unwrap();
+        let paths = names.map(|name| dir.path().join("root").join(name));
+        for path in &paths {
+            fs::write(path, b"same bytes").unwrap();
+        }
+
+        let group = DuplicateGroup {
+            hash: hash_file(&paths[0]).unwrap(),
+            size: 10,
+            paths: paths.to_vec(),
+            modified: HashMap::new(),
+        };
+        let mut plan = Plan::new();
+        plan.add_group(&group, &[1]);
+
+        (dir, paths, plan)
+    }
+
+    fn run(dir: &TempDir, script: &[u8]) -> bool {
+        let path = dir.path().join("cleanup.sh");
+        fs::write(&path, script).unwrap();
+        Command::new("sh").arg(&path).status().unwrap().success()
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote(Path::new("/a b")), b"'/a b'");
+        assert_eq!(quote(Path::new("it's")), b"'it'\\''s'");
+    }
+
+    #[test]
+    fn test_rm_script_removes_duplicate() {
+        let (dir, paths, plan) = setup(["a.txt", "it's a\nweird $(name).txt"]);
+
+        let script = generate(&plan, ScriptCommand::Rm, None).unwrap();
+
+        assert!(run(&dir, &script));
+        assert!(paths[0].exists());
+        assert!(!paths[1].exists());
+    }
+
+    #[test]
+    fn test_script_handles_non_utf8_paths() {
+        let dir = TempDir::new().unwrap();
+        let keeper = dir.path().join("a.txt");
+        let victim = dir.path().join(OsStr::from_bytes(b"caf\xe9.txt"));
+        fs::write(&keeper, b"same").unwrap();
+        fs::write(&victim, b"same").unwrap();
+        let group = DuplicateGroup {
+            hash: hash_file(&keeper).unwrap(),
+            size: 4,
+            paths: vec![keeper.clone(), victim.clone()],
+            modified: HashMap::new(),
+        };
+        let mut plan = Plan::new();
+        plan.add_group(&group, &[1]);
+
+        assert!(run(
+            &dir,
+            &generate(&plan, ScriptCommand::Rm, None).unwrap()
+        ));
+        assert!(!victim.exists());
+    }
+
+    #[test]
+    fn test_script_aborts_when_file_changed() {
+        let (dir, paths, plan) = setup(["a.txt", "b.txt"]);
+        let script = generate(&plan, ScriptCommand::Rm, None).unwrap();
+
+        fs::write(&paths[1], b"SAME BYTES").unwrap();
+
+        assert!(!run(&dir, &script));
+        assert!(paths[1].exists());
+    }
+
+    #[test]
+    fn test_ln_script_hard_links_duplicate() {
+        use std::os::unix::fs::MetadataExt;
+        let (dir, paths, plan) = setup(["a.txt", "b.txt"]);
+
+        assert!(run(
+            &dir,
+            &generate(&plan, ScriptCommand::Ln, None).unwrap()
+        ));
+
+        let a = fs::metadata(&paths[0]).unwrap();
+        let b = fs::metadata(&paths[1]).unwrap();
+        assert_eq!(a.ino(), b.ino());
+    }
+
+    #[test]
+    fn test_ln_without_keeper_rejected() {
+        let (_dir, paths, _plan) = setup(["a.txt", "b.txt"]);
+        let group = DuplicateGroup {
+            hash: hash_file(&paths[0]).unwrap(),
+            size: 10,
+            paths: paths.to_vec(),
+            modified: HashMap::new(),
+        };
+        let mut plan = Plan::new();
+        plan.add_group(&group, &[0, 1]);
+
+        assert!(generate(&plan, ScriptCommand::Ln, None).is_err());
+        assert!(generate(&plan, ScriptCommand::Rm, None).is_ok());
+    }
+
+    #[test]
+    fn test_mv_script_writes_quarantine_manifest() {
+        let (dir, paths, plan) = setup(["a.txt", "it's b.txt"]);
+        let target = MoveTarget {
+            quarantine: dir.path().join("q"),
+            root: dir.path().join("root"),
+        };
+
+        let script = generate(&plan, ScriptCommand::Mv, Some(&target)).unwrap();
+
+        assert!(run(&dir, &script));
+        assert!(!paths[1].exists());
+        assert!(target.quarantine.join("it's b.txt").exists());
+        let entries = read_manifest(&target.quarantine).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].original, paths[1]);
+    }
+
+    #[test]
+    fn test_mv_without_target_rejected() {
+        let (_dir, _paths, plan) = setup(["a.txt", "b.txt"]);
+
+        assert!(generate(&plan, ScriptCommand::Mv, None).is_err());
+    }
+}
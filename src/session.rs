@@ -0,0 +1,406 @@
+//! Review sessions saved to disk.
+//!
+//! Reviewing thousands of groups rarely fits in one sitting. A session file
+//! holds the results of a scan together with the keep/delete decisions made
+//! so far, so a review can be reopened days later, or by someone else,
+//! without scanning again. Files are revalidated when a session is opened:
+//! copies that disappeared or changed since the scan are dropped, along with
+//! the decisions about them.
+//!
+//! The session is a tab-separated text file with one record per line:
+//!
+//! - `root\t<dir>`: the scanned directory, always the first line
+//! - `group\t<hash>\t<size>`: a duplicate group
+//! - `path\t<decision>\t<mtime>\t<path>`: a copy in the preceding group,
+//!   with `decision` one of `keep`, `delete` or `-` if undecided, and
+//!   `mtime` as `seconds.nanoseconds` or `-` if unknown
+//!
+//! Paths are percent-encoded.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::duplicates::DuplicateGroup;
+use crate::format::{decode_mtime, decode_path, encode_mtime, encode_path};
+
+/// What the reviewer decided for one copy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Keep,
+    Delete,
+}
+
+impl Decision {
+    fn as_str(self) -> &'static str {
+        match self {
+            Decision::Keep => "keep",
+            Decision::Delete => "delete",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Option<Self>> {
+        match s {
+            "keep" => Some(Some(Decision::Keep)),
+            "delete" => Some(Some(Decision::Delete)),
+            "-" => Some(None),
+            _ => None,
+        }
+    }
+}
+
+/// Scan results and review decisions, saved to a session file.
+#[derive(Debug)]
+pub struct Session {
+    /// Location of the session file.
+    path: PathBuf,
+
+    /// Directory that was scanned.
+    pub root: PathBuf,
+
+    /// Duplicate groups, in the order they were listed.
+    pub groups: Vec<DuplicateGroup>,
+
+    /// Decisions made so far, by path. Undecided copies have no entry.
+    decisions: HashMap<PathBuf, Decision>,
+}
+
+impl Session {
+    /// Starts a session at `path` for the results of a scan of `root`.
+    ///
+    /// The root and the paths in `groups` are made absolute, so the session
+    /// can be reopened from another working directory.
+    pub fn new(path: &Path, root: &Path, groups: Vec<DuplicateGroup>) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            root: absolute(root),
+            groups: absolute_groups(groups),
+            decisions: HashMap::new(),
+        }
+    }
+
+    /// Reads the session saved at `path`.
+    ///
+    /// # Returns
+    ///
+    /// The session, `None` if there is none, or an error if the file is
+    /// malformed.
+    pub fn open(path: &Path) -> io::Result<Option<Self>> {
+        let contents = match fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let malformed = |line: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("malformed session line: {}", line),
+            )
+        };
+
+        let mut lines = contents.lines();
+        let first = lines.next().unwrap_or_default();
+        let root = match parse_line(first) {
+            Some(Record::Root(root)) => root,
+            _ => return Err(malformed(first)),
+        };
+
+        let mut session = Session::new(path, &root, Vec::new());
+        for line in lines {
+            match parse_line(line).ok_or_else(|| malformed(line))? {
+                Record::Group(hash, size) => session.groups.push(DuplicateGroup {
+                    hash,
+                    size,
+                    paths: Vec::new(),
+                    modified: HashMap::new(),
+                }),
+                Record::Path(decision, modified, path) => {
+                    let group = session.groups.last_mut().ok_or_else(|| malformed(line))?;
+                    if let Some(modified) = modified {
+                        group.modified.insert(path.clone(), modified);
+                    }
+                    if let Some(decision) = decision {
+                        session.decisions.insert(path.clone(), decision);
+                    }
+                    group.paths.push(path);
+                }
+                Record::Root(_) => return Err(malformed(line)),
+            }
+        }
+
+        Ok(Some(session))
+    }
+
+    /// Location of the session file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Drops copies that no longer match the scan, and groups left with a
+    /// single copy.
+    ///
+    /// A copy no longer matches if it is gone, is not a regular file any
+    /// more, or its size or modification time changed.
+    ///
+    /// # Returns
+    ///
+    /// The number of copies dropped.
+    pub fn revalidate(&mut self) -> usize {
+        let mut dropped = 0;
+        for group in &mut self.groups {
+            group.paths.retain(|path| {
+                let unchanged = fs::symlink_metadata(path).is_ok_and(|metadata| {
+                    metadata.is_file()
+                        && metadata.len() == group.size
+                        && group
+                            .modified
+                            .get(path)
+                            .is_none_or(|&time| metadata.modified().ok() == Some(time))
+                });
+                if !unchanged {
+                    group.modified.remove(path);
+                    self.decisions.remove(path);
+                    dropped += 1;
+                }
+                unchanged
+            });
+        }
+
+        self.groups.retain(|group| {
+            if group.paths.len() > 1 {
+                return true;
+            }
+            for path in &group.paths {
+                self.decisions.remove(path);
+            }
+            false
+        });
+        dropped
+    }
+
+    /// Replaces the groups with those of a new scan.
+    ///
+    /// Decisions about copies that are still duplicates are kept.
+    pub fn replace_groups(&mut self, groups: Vec<DuplicateGroup>) {
+        let groups = absolute_groups(groups);
+        let paths: Vec<&PathBuf> = groups.iter().flat_map(|g| &g.paths).collect();
+        let mut decisions = HashMap::new();
+        for path in paths {
+            if let Some(&decision) = self.decisions.get(path) {
+                decisions.insert(path.clone(), decision);
+            }
+        }
+        self.decisions = decisions;
+        self.groups = groups;
+    }
+
+    /// Returns `true` if a decision was made about some copy in `group`.
+    pub fn is_decided(&self, group: &DuplicateGroup) -> bool {
+        group.paths.iter().any(|p| self.decisions.contains_key(p))
+    }
+
+    /// Whether each copy in `group` was decided to be deleted.
+    pub fn marks(&self, group: &DuplicateGroup) -> Vec<bool> {
+        group
+            .paths
+            .iter()
+            .map(|p| self.decisions.get(p) == Some(&Decision::Delete))
+            .collect()
+    }
+
+    /// Records deleting the copies of `group` flagged in `delete` and keeping
+    /// the others.
+    pub fn decide(&mut self, group: &DuplicateGroup, delete: &[bool]) {
+        for (path, &delete) in group.paths.iter().zip(delete) {
+            let decision = if delete {
+                Decision::Delete
+            } else {
+                Decision::Keep
+            };
+            self.decisions.insert(path.clone(), decision);
+        }
+    }
+
+    /// Number of copies with a decision.
+    pub fn decision_count(&self) -> usize {
+        self.decisions.len()
+    }
+
+    /// Writes the session to its file, replacing the previous contents.
+    pub fn save(&self) -> io::Result<()> {
+        let mut contents = format!("root\t{}\n", encode_path(&self.root));
+        for group in &self.groups {
+            contents.push_str(&format!("group\t{}\t{}\n", group.hash, group.size));
+            for path in &group.paths {
+                contents.push_str(&format!(
+                    "path\t{}\t{}\t{}\n",
+                    self.decisions.get(path).map_or("-", |d| d.as_str()),
+                    encode_mtime(group.modified.get(path).copied()),
+                    encode_path(path)
+                ));
+            }
+        }
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        fs::write(&tmp, contents)?;
+        fs::rename(tmp, &self.path)
+    }
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Makes the paths of `groups` absolute.
+fn absolute_groups(mut groups: Vec<DuplicateGroup>) -> Vec<DuplicateGroup> {
+    for group in &mut groups {
+        for path in &mut group.paths {
+            *path = absolute(path);
+        }
+        group.modified = group
+            .modified
+            .drain()
+            .map(|(path, time)| (absolute(&path), time))
+            .collect();
+    }
+    groups
+}
+
+/// A parsed session line.
+enum Record {
+    Root(PathBuf),
+    Group(String, u64),
+    Path(Option<Decision>, Option<SystemTime>, PathBuf),
+}
+
+fn parse_line(line: &str) -> Option<Record> {
+    let mut fields = line.split('\t');
+    let record = match fields.next()? {
+        "root" => Record::Root(decode_path(fields.next()?)?),
+        "group" => {
+            let hash = fields.next()?.to_string();
+            Record::Group(hash, fields.next()?.parse().ok()?)
+        }
+        "path" => {
+            let decision = Decision::parse(fields.next()?)?;
+            let modified = decode_mtime(fields.next()?)?;
+            Record::Path(decision, modified, decode_path(fields.next()?)?)
+        }
+        _ => return None,
+    };
+
+    fields.next().is_none().then_some(record)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Creates two groups of two copies each and a session for them.
+    fn setup() -> (TempDir, Session) {
+        let dir = TempDir::new().unwrap();
+        let mut groups = Vec::new();
+        for (name, contents) in [("a", "aaaa"), ("b", "bb")] {
+            let mut group = DuplicateGroup {
+                hash: format!("hash-{}", name),
+                size: contents.len() as u64,
+                paths: Vec::new(),
+                modified: HashMap::new(),
+            };
+            for copy in ["1", "2 copy"] {
+                let path = dir.path().join(format!("{}{}", name, copy));
+                fs::write(&path, contents).unwrap();
+                let modified = fs::metadata(&path).unwrap().modified().unwrap();
+                group.modified.insert(path.clone(), modified);
+                group.paths.push(path);
+            }
+            groups.push(group);
+        }
+
+        let session = Session::new(&dir.path().join("review.session"), dir.path(), groups);
+        (dir, session)
+    }
+
+    #[test]
+    fn test_session_roundtrip() {
+        let (dir, mut session) = setup();
+        let first = session.groups[0].clone();
+        session.decide(&first, &[false, true]);
+        session.save().unwrap();
+
+        let opened = Session::open(session.path()).unwrap().unwrap();
+
+        assert_eq!(opened.root, dir.path());
+        assert_eq!(opened.groups.len(), 2);
+        assert_eq!(opened.groups[0].paths, first.paths);
+        assert_eq!(opened.groups[0].modified, first.modified);
+        assert_eq!(opened.marks(&opened.groups[0]), vec![false, true]);
+        assert!(opened.is_decided(&opened.groups[0]));
+        assert!(!opened.is_decided(&opened.groups[1]));
+        assert!(Session::open(&dir.path().join("missing"))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_relative_paths_are_saved_absolute() {
+        let (dir, session) = setup();
+        let relative = |path: &Path| {
+            let cwd = std::env::current_dir().unwrap();
+            let depth = cwd.components().count() - 1;
+            let up: PathBuf = std::iter::repeat_n("..", depth).collect();
+            up.join(path.strip_prefix("/").unwrap())
+        };
+        let mut groups = session.groups.clone();
+        for group in &mut groups {
+            group.paths = group.paths.iter().map(|p| relative(p)).collect();
+            group.modified = group
+                .modified
+                .iter()
+                .map(|(p, &t)| (relative(p), t))
+                .collect();
+        }
+
+        let session = Session::new(session.path(), &relative(dir.path()), groups);
+
+        assert!(session.root.is_absolute());
+        assert!(session.groups.iter().all(|g| g
+            .paths
+            .iter()
+            .chain(g.modified.keys())
+            .all(|p| p.is_absolute())));
+    }
+
+    #[test]
+    fn test_revalidate_drops_changed_files() {
+        let (dir, mut session) = setup();
+        let first = session.groups[0].clone();
+        session.decide(&first, &[false, true]);
+
+        fs::remove_file(dir.path().join("a2 copy")).unwrap();
+        fs::write(dir.path().join("b1"), "changed").unwrap();
+
+        assert_eq!(session.revalidate(), 2);
+        assert!(session.groups.is_empty());
+        assert_eq!(session.decision_count(), 0);
+    }
+
+    #[test]
+    fn test_replace_groups_keeps_decisions() {
+        let (_dir, mut session) = setup();
+        let first = session.groups[0].clone();
+        session.decide(&first, &[true, false]);
+
+        session.replace_groups(vec![first.clone()]);
+
+        assert_eq!(session.marks(&first), vec![true, false]);
+        assert_eq!(session.decision_count(), 2);
+    }
+}
@@ -0,0 +1,10 @@
+//! Helpers shared by the unit tests.
+
+use std::fs;
+use std::path::Path;
+
+/// Writes `contents` to `path`, creating its parent directories first.
+pub fn write(path: &Path, contents: impl AsRef<[u8]>) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, contents).unwrap();
+}
@@ -0,0 +1,280 @@
+//! Freedesktop.org Trash support.
+//!
+//! Implements the [Trash specification] so that files removed by this tool
+//! show up in desktop trash UIs and can be restored from there. Files on the
+//! same filesystem as the home directory go to the home trash; files on other
+//! volumes go to a per-volume `.Trash/$uid` or `.Trash-$uid` directory.
+//!
+//! [Trash specification]: https://specifications.freedesktop.org/trash-spec/latest/
+
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, DirBuilder, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::format::{encode_path, local_timestamp};
+
+/// Mode bit required on a shared `$topdir/.Trash` directory.
+const STICKY_BIT: u32 = 0o1000;
+
+/// A trash directory containing `files/` and `info/` subdirectories.
+#[derive(Debug, Clone)]
+pub struct TrashDir {
+    /// Root of the trash directory (e.g. `~/.local/share/Trash`).
+    pub root: PathBuf,
+
+    /// Top directory of the volume for per-volume trash directories.
+    ///
+    /// Original paths are recorded relative to it. `None` for the home
+    /// trash, where original paths are recorded as absolute paths.
+    pub topdir: Option<PathBuf>,
+}
+
+impl TrashDir {
+    /// Directory holding the trashed files themselves.
+    pub fn files_dir(&self) -> PathBuf {
+        self.root.join("files")
+    }
+
+    /// Directory holding the `.trashinfo` metadata files.
+    pub fn info_dir(&self) -> PathBuf {
+        self.root.join("info")
+    }
+
+    /// Moves a file into this trash directory.
+    ///
+    /// Writes the `.trashinfo` file first, as required by the specification,
+    /// and removes it again if the file cannot be moved.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Absolute path of the file to trash.
+    ///
+    /// # Returns
+    ///
+    /// The new location of the file inside `files/`, or an IO error.
+    pub fn trash(&self, path: &Path) -> io::Result<PathBuf> {
+        let name = path
+            .file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+
+        create_private_dir(&self.files_dir())?;
+        create_private_dir(&self.info_dir())?;
+
+        let recorded = match &self.topdir {
+            Some(topdir) => path.strip_prefix(topdir).unwrap_or(path),
+            None => path,
+        };
+        let contents = format!(
+            "[Trash Info]\nPath={}\nDeletionDate={}\n",
+            encode_path(recorded),
+            local_timestamp(SystemTime::now())
+        );
+
+        let (trash_name, info_path, mut info_file) = self.reserve_name(name)?;
+        let target = self.files_dir().join(trash_name);
+
+        let moved = info_file
+            .write_all(contents.as_bytes())
+            .and_then(|()| fs::rename(path, &target));
+        if let Err(e) = moved {
+            let _ = fs::remove_file(&info_path);
+            return Err(e);
+        }
+
+        Ok(target)
+    }
+
+    /// Atomically claims a unique name in the trash by creating its
+    /// `.trashinfo` file with `O_EXCL`.
+    fn reserve_name(&self, name: &OsStr) -> io::Result<(OsString, PathBuf, File)> {
+        let mut counter = 1u32;
+
+        loop {
+            let mut candidate = name.to_os_string();
+            if counter > 1 {
+                candidate.push(format!(".{}", counter));
+            }
+            counter += 1;
+
+            if fs::symlink_metadata(self.files_dir().join(&candidate)).is_ok() {
+                continue;
+            }
+
+            let mut info_name = candidate.clone();
+            info_name.push(".trashinfo");
+            let info_path = self.info_dir().join(info_name);
+
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&info_path)
+            {
+                Ok(file) => return Ok((candidate, info_path, file)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Moves a file to the trash directory appropriate for its filesystem.
+///
+/// # Returns
+///
+/// The new location of the file inside the trash, or an IO error.
+pub fn move_to_trash(path: &Path) -> io::Result<PathBuf> {
+    let path = absolute_path(path)?;
+    let device = fs::symlink_metadata(&path)?.dev();
+
+    trash_dir_for(&path, device)?.trash(&path)
+}
+
+/// Resolves the parent directory of `path` without following the file itself.
+fn absolute_path(path: &Path) -> io::Result<PathBuf> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    let parent = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+
+    Ok(fs::canonicalize(parent)?.join(name))
+}
+
+/// Picks the trash directory for a file living on `device`.
+fn trash_dir_for(path: &Path, device: u64) -> io::Result<TrashDir> {
+    if let Some(home) = home_trash() {
+        if nearest_device(&home) == Some(device) {
+            return Ok(TrashDir {
+                root: home,
+                topdir: None,
+            });
+        }
+    }
+
+    let topdir = mount_point(path, device);
+    // SAFETY: getuid has no preconditions and cannot fail.
+    let uid = unsafe { libc::getuid() };
+
+    let shared = topdir.join(".Trash");
+    if let Ok(meta) = fs::symlink_metadata(&shared) {
+        let user_dir = shared.join(uid.to_string());
+        if meta.is_dir() && meta.mode() & STICKY_BIT != 0 && create_private_dir(&user_dir).is_ok() {
+            return Ok(TrashDir {
+                root: user_dir,
+                topdir: Some(topdir),
+            });
+        }
+    }
+
+    Ok(TrashDir {
+        root: topdir.join(format!(".Trash-{}", uid)),
+        topdir: Some(topdir),
+    })
+}
+
+/// Returns the home trash directory, `$XDG_DATA_HOME/Trash`.
+fn home_trash() -> Option<PathBuf> {
+    match env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir).join("Trash")),
+        _ => env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share/Trash")),
+    }
+}
+
+/// Returns the device of `path`, or of its closest existing ancestor.
+fn nearest_device(path: &Path) -> Option<u64> {
+    path.ancestors()
+        .find_map(|p| fs::metadata(p).ok())
+        .map(|meta| meta.dev())
+}
+
+/// Walks up from `path` to the topmost ancestor still on `device`.
+fn mount_point(path: &Path, device: u64) -> PathBuf {
+    let mut top = path.parent().unwrap_or(path);
+
+    for ancestor in path.ancestors().skip(1) {
+        match fs::metadata(ancestor) {
+            Ok(meta) if meta.dev() == device => top = ancestor,
+            _ => break,
+        }
+    }
+
+    top.to_path_buf()
+}
+
+/// Creates a directory (and its parents) readable only by the current user.
+fn create_private_dir(path: &Path) -> io::Result<()> {
+    DirBuilder::new().recursive(true).mode(0o700).create(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_trash_writes_info_and_moves_file() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("song one.mp3");
+        fs::write(&file, b"data").unwrap();
+
+        let trash = TrashDir {
+            root: dir.path().join("Trash"),
+            topdir: None,
+        };
+        let target = trash.trash(&file).unwrap();
+
+        assert!(!file.exists());
+        assert_eq!(fs::read(&target).unwrap(), b"data");
+
+        let info = fs::read_to_string(trash.info_dir().join("song one.mp3.trashinfo")).unwrap();
+        assert!(info.starts_with("[Trash Info]\n"));
+        assert!(info.contains(&format!("Path={}\n", encode_path(&file))));
+        assert!(info.contains("DeletionDate="));
+    }
+
+    #[test]
+    fn test_trash_relative_path_for_volume_trash() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("album")).unwrap();
+        let file = dir.path().join("album/cover.jpg");
+        fs::write(&file, b"img").unwrap();
+
+        let trash = TrashDir {
+            root: dir.path().join(".Trash-1000"),
+            topdir: Some(dir.path().to_path_buf()),
+        };
+        trash.trash(&file).unwrap();
+
+        let info = fs::read_to_string(trash.info_dir().join("cover.jpg.trashinfo")).unwrap();
+        assert!(info.contains("Path=album/cover.jpg\n"));
+    }
+
+    #[test]
+    fn test_trash_name_collision() {
+        let dir = TempDir::new().unwrap();
+        let trash = TrashDir {
+            root: dir.path().join("Trash"),
+            topdir: None,
+        };
+
+        for sub in ["a", "b"] {
+            fs::create_dir(dir.path().join(sub)).unwrap();
+            let file = dir.path().join(sub).join("same.txt");
+            fs::write(&file, sub).unwrap();
+            trash.trash(&file).unwrap();
+        }
+
+        assert_eq!(fs::read(trash.files_dir().join("same.txt")).unwrap(), b"a");
+        assert_eq!(
+            fs::read(trash.files_dir().join("same.txt.2")).unwrap(),
+            b"b"
+        );
+        assert!(trash.info_dir().join("same.txt.2.trashinfo").exists());
+    }
+}
@@ -0,0 +1,345 @@
+//! Duplicate directory detection.
+//!
+//! Builds on the file-level [`DuplicateGroup`]s to find whole directory trees
+//! with identical contents. Every directory gets a Merkle-style hash over the
+//! names, types and hashes of its entries, so two directories hash the same
+//! exactly when they contain the same names with the same content all the way
+//! down. A file that belongs to no duplicate group is unique, which makes
+//! every directory above it unique as well.
+//!
+//! Only the highest level of a duplication is reported: when `a/` and `b/`
+//! are identical, their identical subdirectories are not listed again.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+use md5::{Digest, Md5};
+use walkdir::WalkDir;
+
+use crate::actions::{Operation, Plan, EMPTY_HASH};
+use crate::duplicates::DuplicateGroup;
+use crate::scanner::Empties;
+
+/// A set of directories whose trees have identical contents.
+#[derive(Debug, Clone)]
+pub struct DirGroup {
+    /// Merkle hash shared by all directories in the group.
+    pub hash: String,
+
+    /// Total size of the files in one copy, in bytes.
+    pub size: u64,
+
+    /// Number of files in one copy.
+    pub file_count: usize,
+
+    /// The identical directories.
+    pub dirs: Vec<PathBuf>,
+}
+
+impl DirGroup {
+    /// Bytes that could be recovered by keeping only one copy.
+    pub fn wasted_space(&self) -> u64 {
+        self.size * (self.dirs.len().saturating_sub(1) as u64)
+    }
+
+    /// Plans removing every file in `dirs[remove]`, keeping the twins in
+    /// `dirs[keep]`.
+    ///
+    /// Each removal records its counterpart in the kept directory as the
+    /// keeper, so it is re-verified before removal and can be undone.
+    ///
+    /// # Returns
+    ///
+    /// The plan, or an error if the directory no longer matches the scan.
+    pub fn plan_removal(
+        &self,
+        keep: usize,
+        remove: usize,
+        groups: &[DuplicateGroup],
+    ) -> io::Result<Plan> {
+        let keep_dir = &self.dirs[keep];
+        let victim_dir = &self.dirs[remove];
+        let by_path: HashMap<&Path, &DuplicateGroup> = groups
+            .iter()
+            .flat_map(|g| g.paths.iter().map(move |p| (p.as_path(), g)))
+            .collect();
+
+        let mut plan = Plan::new();
+        for entry in WalkDir::new(victim_dir).sort_by_file_name() {
+            let entry = entry?;
+            if entry.file_type().is_dir() {
+                continue;
+            }
+
+            let victim = entry.path();
+            let relative = victim.strip_prefix(victim_dir).map_err(io::Error::other)?;
+            let keeper = keep_dir.join(relative);
+
+            let (size, hash, victim_modified, keeper_modified) = match by_path.get(victim) {
+                Some(group) => (
+                    group.size,
+                    group.hash.clone(),
+                    group.modified.get(victim).copied(),
+                    group.modified.get(&keeper).copied(),
+                ),
+                None if entry.file_type().is_file() && entry.metadata()?.len() == 0 => {
+                    (0, EMPTY_HASH.to_string(), None, None)
+                }
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("{} changed since the scan", victim.display()),
+                    ));
+                }
+            };
+
+            plan.operations.push(Operation {
+                victim: victim.to_path_buf(),
+                keeper: Some(keeper),
+                size,
+                hash,
+                victim_modified,
+                keeper_modified,
+            });
+        }
+
+        Ok(plan)
+    }
+}
+
+/// What is known about one directory while hashing its parent.
+#[derive(Default)]
+struct DirState {
+    /// `(name, kind, hash)` of each entry, in file name order.
+    entries: Vec<(Vec<u8>, u8, String)>,
+    size: u64,
+    file_count: usize,
+    /// Set when the tree holds something with no duplicate anywhere.
+    unique: bool,
+}
+
+/// Finds directory trees under `root` whose contents are identical.
+///
+/// # Arguments
+///
+/// * `root` - Directory that was scanned.
+/// * `groups` - File duplicate groups from the same scan.
+/// * `empties` - Empty files from the same scan, which are all identical.
+///
+/// # Returns
+///
+/// Groups of two or more identical, non-empty directories, highest level
+/// only, ordered by recoverable space.
+pub fn find_duplicate_trees(
+    root: &Path,
+    groups: &[DuplicateGroup],
+    empties: &Empties,
+) -> Vec<DirGroup> {
+    if groups.is_empty() {
+        return Vec::new();
+    }
+
+    let mut known: HashMap<&Path, (&str, u64)> = HashMap::new();
+    for group in groups {
+        for path in &group.paths {
+            known.insert(path, (&group.hash, group.size));
+        }
+    }
+    for file in &empties.files {
+        known.insert(&file.path, (EMPTY_HASH, 0));
+    }
+
+    let mut states: HashMap<PathBuf, DirState> = HashMap::new();
+    let mut hashed: BTreeMap<String, Vec<(PathBuf, u64, usize)>> = BTreeMap::new();
+
+    for entry in WalkDir::new(root).sort_by_file_name().contents_first(true) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                // An unreadable directory may hide anything.
+                if let Some(path) = e.path() {
+                    for dir in [Some(path), path.parent()].into_iter().flatten() {
+                        states.entry(dir.to_path_buf()).or_default().unique = true;
+                    }
+                }
+                continue;
+            }
+        };
+
+        let path = entry.path();
+        let name = entry.file_name().as_bytes().to_vec();
+        let (kind, hash, size, files, unique) = if entry.file_type().is_dir() {
+            let state = states.remove(path).unwrap_or_default();
+            let hash = (!state.unique).then(|| tree_hash(&state.entries));
+            if let Some(hash) = &hash {
+                if state.file_count > 0 {
+                    hashed.entry(hash.clone()).or_default().push((
+                        path.to_path_buf(),
+                        state.size,
+                        state.file_count,
+                    ));
+                }
+            }
+            (b'd', hash, state.size, state.file_count, state.unique)
+        } else if entry.file_type().is_file() {
+            match known.get(path) {
+                Some(&(hash, size)) => (b'f', Some(hash.to_string()), size, 1, false),
+                None => (b'f', None, 0, 1, true),
+            }
+        } else {
+            (b'o', None, 0, 0, true)
+        };
+
+        if entry.depth() == 0 {
+            continue;
+        }
+        let Some(parent) = path.parent() else {
+            continue;
+        };
+        let state = states.entry(parent.to_path_buf()).or_default();
+        state.unique |= unique;
+        state.size += size;
+        state.file_count += files;
+        if let Some(hash) = hash {
+            state.entries.push((name, kind, hash));
+        }
+    }
+
+    let duplicated: HashSet<&Path> = hashed
+        .values()
+        .filter(|dirs| dirs.len() > 1)
+        .flatten()
+        .map(|(path, _, _)| path.as_path())
+        .collect();
+
+    let mut trees: Vec<DirGroup> = hashed
+        .iter()
+        .filter(|(_, dirs)| dirs.len() > 1)
+        .filter(|(_, dirs)| {
+            // Skip groups that are already covered by an identical parent.
+            dirs.iter()
+                .any(|(path, _, _)| !path.parent().is_some_and(|p| duplicated.contains(p)))
+        })
+        .map(|(hash, dirs)| DirGroup {
+            hash: hash.clone(),
+            size: dirs[0].1,
+            file_count: dirs[0].2,
+            dirs: dirs.iter().map(|(path, _, _)| path.clone()).collect(),
+        })
+        .collect();
+
+    for tree in &mut trees {
+        tree.dirs.sort();
+    }
+    trees.sort_by_cached_key(|t| (Reverse(t.wasted_space()), t.dirs[0].clone(), t.hash.clone()));
+    trees
+}
+
+/// Hashes a directory's entries into a single Merkle hash.
+fn tree_hash(entries: &[(Vec<u8>, u8, String)]) -> String {
+    let mut hasher = Md5::new();
+    for (name, kind, hash) in entries {
+        hasher.update([*kind]);
+        hasher.update(name);
+        hasher.update([0]);
+        hasher.update(hash.as_bytes());
+        hasher.update([b'\n']);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::Runner;
+    use crate::duplicates::find_duplicates;
+    use crate::scanner::{no_progress, scan_directory};
+    use crate::test_util::write;
+    use tempfile::TempDir;
+
+    fn analyze(root: &Path) -> (Vec<DuplicateGroup>, Vec<DirGroup>) {
+        let scan = scan_directory(root, &mut no_progress).unwrap();
+        let groups = find_duplicates(scan.files, &HashMap::new(), &mut no_progress);
+        let trees = find_duplicate_trees(root, &groups, &scan.empties);
+        (groups, trees)
+    }
+
+    /// Creates two identical project copies and a partial third copy.
+    fn setup() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        for copy in ["a/project", "b/project-copy"] {
+            let base = dir.path().join(copy);
+            write(&base.join("README"), b"readme");
+            write(&base.join("src/main.rs"), b"fn main() {}");
+            write(&base.join("src/empty.rs"), b"");
+        }
+        write(&dir.path().join("c/src/main.rs"), b"fn main() {}");
+        write(&dir.path().join("c/src/empty.rs"), b"");
+        write(&dir.path().join("c/src/extra.rs"), b"unique");
+        dir
+    }
+
+    #[test]
+    fn test_reports_highest_identical_level_only() {
+        let dir = setup();
+
+        let (_, trees) = analyze(dir.path());
+
+        assert_eq!(trees.len(), 1);
+        assert_eq!(
+            trees[0].dirs,
+            vec![
+                dir.path().join("a/project"),
+                dir.path().join("b/project-copy")
+            ]
+        );
+        assert_eq!(trees[0].file_count, 3);
+        assert_eq!(trees[0].size, 18);
+    }
+
+    #[test]
+    fn test_names_are_part_of_the_hash() {
+        let dir = TempDir::new().unwrap();
+        write(&dir.path().join("a/x.txt"), b"same");
+        write(&dir.path().join("b/y.txt"), b"same");
+
+        let (groups, trees) = analyze(dir.path());
+
+        assert_eq!(groups.len(), 1);
+        assert!(trees.is_empty());
+    }
+
+    #[test]
+    fn test_plan_removal_keeps_twin_files() {
+        let dir = setup();
+        let (groups, trees) = analyze(dir.path());
+
+        let plan = trees[0].plan_removal(0, 1, &groups).unwrap();
+
+        assert_eq!(plan.operations.len(), 3);
+        for op in &plan.operations {
+            let relative = op
+                .victim
+                .strip_prefix(dir.path().join("b/project-copy"))
+                .unwrap();
+            assert_eq!(
+                op.keeper.as_deref(),
+                Some(dir.path().join("a/project").join(relative).as_path())
+            );
+        }
+        assert_eq!(Runner::dry_run().run(&plan).removed.len(), 3);
+    }
+
+    #[test]
+    fn test_plan_removal_rejects_new_files() {
+        let dir = setup();
+        let (groups, trees) = analyze(dir.path());
+
+        write(&dir.path().join("b/project-copy/new.txt"), b"new");
+
+        assert!(trees[0].plan_removal(0, 1, &groups).is_err());
+    }
+}
@@ -0,0 +1,556 @@
+//! Full-screen review of duplicate groups.
+//!
+//! Shows every group at once, sorted by wasted space, next to a detail pane
+//! with the paths of the selected group. Files are marked for removal with
+//! the keyboard while a running total shows the space that would be freed,
+//! and nothing is removed until all decisions are applied in a single step.
+//! At least one copy of every group always stays unmarked, and protected
+//! files cannot be marked at all.
+
+use std::io;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::actions::Plan;
+use crate::duplicates::{DuplicateGroup, DuplicateStats};
+use crate::format::local_timestamp;
+use crate::policy::KeepPolicy;
+
+/// Key bindings shown at the bottom of the screen.
+const HELP: &str =
+    "↑↓ move  Tab switch pane  Space mark  K keep only this  a/A auto-mark group/all  \
+                    u/U clear group/all  x apply  q quit";
+
+/// Which pane receives the movement keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pane {
+    Groups,
+    Paths,
+}
+
+/// How the review ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Apply,
+    Quit,
+}
+
+/// Decisions made so far and what is on screen.
+struct Review<'a> {
+    groups: &'a [DuplicateGroup],
+
+    /// Indices into `groups`, most wasted space first.
+    order: Vec<usize>,
+
+    /// Whether each path of each group is marked for removal.
+    marked: Vec<Vec<bool>>,
+
+    /// Whether each path of each group is protected.
+    protected: Vec<Vec<bool>>,
+
+    group_list: ListState,
+    path_list: ListState,
+    focus: Pane,
+
+    /// Set while the apply confirmation is shown.
+    confirming: bool,
+
+    /// Feedback on the last key, such as why a mark was refused.
+    status: String,
+}
+
+impl<'a> Review<'a> {
+    fn new(groups: &'a [DuplicateGroup], policy: &KeepPolicy) -> Self {
+        let mut order: Vec<usize> = (0..groups.len()).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse(groups[i].wasted_space()));
+
+        Self {
+            groups,
+            order,
+            marked: groups.iter().map(|g| vec![false; g.paths.len()]).collect(),
+            protected: groups
+                .iter()
+                .map(|g| g.paths.iter().map(|p| policy.is_protected(p)).collect())
+                .collect(),
+            group_list: ListState::default().with_selected(Some(0)),
+            path_list: ListState::default().with_selected(Some(0)),
+            focus: Pane::Groups,
+            confirming: false,
+            status: String::new(),
+        }
+    }
+
+    /// Starts from earlier decisions, one mark per path of each group.
+    ///
+    /// Marks on protected files are dropped, and so are all marks of a group
+    /// where every copy is marked.
+    fn with_marks(mut self, marked: &[Vec<bool>]) -> Self {
+        for (g, marks) in marked.iter().enumerate() {
+            let marks: Vec<bool> = marks
+                .iter()
+                .zip(&self.protected[g])
+                .map(|(&m, &protected)| m && !protected)
+                .collect();
+            if marks.len() == self.marked[g].len() && marks.contains(&false) {
+                self.marked[g] = marks;
+            }
+        }
+        self
+    }
+
+    /// Index into `groups` of the selected group.
+    fn group(&self) -> usize {
+        self.order[self.group_list.selected().unwrap_or(0)]
+    }
+
+    fn path(&self) -> usize {
+        self.path_list.selected().unwrap_or(0)
+    }
+
+    /// Toggles the removal mark of the selected path.
+    fn toggle(&mut self) {
+        let (g, p) = (self.group(), self.path());
+        if self.protected[g][p] {
+            self.status = "Protected files cannot be marked".to_string();
+        } else if !self.marked[g][p] && self.marked[g].iter().filter(|&&m| !m).count() == 1 {
+            self.status = "At least one copy must be kept".to_string();
+        } else {
+            self.marked[g][p] = !self.marked[g][p];
+        }
+    }
+
+    /// Keeps only the selected path of its group, besides protected ones.
+    fn keep_only(&mut self) {
+        let (g, keep) = (self.group(), self.path());
+        self.mark_all_but(g, keep);
+    }
+
+    /// Marks every path of group `g` except `keep` and protected ones.
+    fn mark_all_but(&mut self, g: usize, keep: usize) {
+        for (p, marked) in self.marked[g].iter_mut().enumerate() {
+            *marked = p != keep && !self.protected[g][p];
+        }
+    }
+
+    /// Marks the selected group, or all groups, as the keep rules decide.
+    fn auto_mark(&mut self, policy: &KeepPolicy, all: bool) {
+        let targets = if all {
+            (0..self.groups.len()).collect()
+        } else {
+            vec![self.group()]
+        };
+        for g in targets {
+            if let Some(keep) = policy.choose(&self.groups[g].paths) {
+                self.mark_all_but(g, keep);
+            }
+        }
+    }
+
+    /// Clears the marks of the selected group, or of all groups.
+    fn clear(&mut self, all: bool) {
+        if all {
+            self.marked.iter_mut().flatten().for_each(|m| *m = false);
+        } else {
+            let g = self.group();
+            self.marked[g].fill(false);
+        }
+    }
+
+    /// Number of files marked for removal.
+    fn marked_count(&self) -> usize {
+        self.marked.iter().flatten().filter(|&&m| m).count()
+    }
+
+    /// Bytes freed if every marked file is removed.
+    fn freed(&self) -> u64 {
+        self.groups
+            .iter()
+            .zip(&self.marked)
+            .map(|(group, marked)| group.size * marked.iter().filter(|&&m| m).count() as u64)
+            .sum()
+    }
+
+    /// Plans the removal of every marked file, in the displayed order.
+    fn plan(&self) -> Plan {
+        let mut plan = Plan::new();
+        for &g in &self.order {
+            let indices: Vec<usize> = (0..self.marked[g].len())
+                .filter(|&p| self.marked[g][p])
+                .collect();
+            if !indices.is_empty() {
+                plan.add_group(&self.groups[g], &indices);
+            }
+        }
+        plan
+    }
+
+    /// Handles a key press, returning how the review ends if it does.
+    fn handle(&mut self, key: KeyEvent, policy: &KeepPolicy) -> Option<Outcome> {
+        if self.confirming {
+            self.confirming = false;
+            return match key.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') => Some(Outcome::Apply),
+                _ => None,
+            };
+        }
+
+        self.status.clear();
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Some(Outcome::Quit),
+            KeyCode::Up | KeyCode::Char('k') => self.step(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.step(1),
+            KeyCode::PageUp => self.step(-10),
+            KeyCode::PageDown => self.step(10),
+            KeyCode::Tab | KeyCode::BackTab => {
+                self.focus = match self.focus {
+                    Pane::Groups => Pane::Paths,
+                    Pane::Paths => Pane::Groups,
+                }
+            }
+            KeyCode::Right | KeyCode::Char('l') | KeyCode::Enter => self.focus = Pane::Paths,
+            KeyCode::Left | KeyCode::Char('h') => self.focus = Pane::Groups,
+            KeyCode::Char(' ') if self.focus == Pane::Paths => self.toggle(),
+            KeyCode::Char('K') if self.focus == Pane::Paths => self.keep_only(),
+            KeyCode::Char(' ') | KeyCode::Char('K') => {
+                self.status = "Select a file in the paths pane first (Tab)".to_string()
+            }
+            KeyCode::Char('a') => self.auto_mark(policy, false),
+            KeyCode::Char('A') => self.auto_mark(policy, true),
+            KeyCode::Char('u') => self.clear(false),
+            KeyCode::Char('U') => self.clear(true),
+            KeyCode::Char('x') if self.marked_count() == 0 => {
+                self.status = "Nothing is marked for removal".to_string()
+            }
+            KeyCode::Char('x') => self.confirming = true,
+            _ => {}
+        }
+        None
+    }
+
+    /// Moves the selection of the focused pane by `delta` entries.
+    fn step(&mut self, delta: isize) {
+        let paths = self.groups[self.group()].paths.len();
+        let (list, len) = match self.focus {
+            Pane::Groups => (&mut self.group_list, self.order.len()),
+            Pane::Paths => (&mut self.path_list, paths),
+        };
+        let current = list.selected().unwrap_or(0) as isize;
+        list.select(Some((current + delta).clamp(0, len as isize - 1) as usize));
+
+        if self.focus == Pane::Groups {
+            self.path_list.select(Some(0));
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [header, body, footer] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(3),
+            Constraint::Length(2),
+        ])
+        .areas(frame.area());
+        let [left, right] =
+            Layout::horizontal([Constraint::Percentage(45), Constraint::Percentage(55)])
+                .areas(body);
+
+        frame.render_widget(
+            Paragraph::new(format!(
+                " {} group(s) · {} file(s) marked · {} to be freed",
+                self.groups.len(),
+                self.marked_count(),
+                DuplicateStats::format_bytes(self.freed())
+            ))
+            .style(Style::default().add_modifier(Modifier::BOLD)),
+            header,
+        );
+
+        let items: Vec<ListItem> = self
+            .order
+            .iter()
+            .map(|&g| {
+                let group = &self.groups[g];
+                let marked = self.marked[g].iter().filter(|&&m| m).count();
+                let name = group.paths[0]
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                ListItem::new(format!(
+                    "{:>10} {:>3}x {}{}",
+                    DuplicateStats::format_bytes(group.wasted_space()),
+                    group.paths.len(),
+                    name,
+                    if marked > 0 {
+                        format!(" [{} marked]", marked)
+                    } else {
+                        String::new()
+                    }
+                ))
+            })
+            .collect();
+        frame.render_stateful_widget(
+            List::new(items)
+                .block(pane_block(
+                    "Groups by wasted space",
+                    self.focus == Pane::Groups,
+                ))
+                .highlight_style(Style::default().add_modifier(Modifier::REVERSED)),
+            left,
+            &mut self.group_list,
+        );
+
+        let g = self.group();
+        let group = &self.groups[g];
+        let items: Vec<ListItem> = group
+            .paths
+            .iter()
+            .enumerate()
+            .map(|(p, path)| {
+                let (tag, color) = if self.protected[g][p] {
+                    ("protected", Color::Cyan)
+                } else if self.marked[g][p] {
+                    ("delete", Color::Red)
+                } else {
+                    ("keep", Color::Green)
+                };
+                let modified = group
+                    .modified
+                    .get(path)
+                    .map(|&m| local_timestamp(m))
+                    .unwrap_or_else(|| "unknown".to_string());
+                ListItem::new(vec![
+                    Line::from(vec![
+                        Span::styled(format!("[{:^9}] ", tag), Style::default().fg(color)),
+                        Span::raw(path.display().to_string()),
+                    ]),
+                    Line::from(format!("            modified {}", modified)),
+                ])
+            })
+            .collect();
+        let title = format!(
+            "{} each, hash {}",
+            DuplicateStats::format_bytes(group.size),
+            group.hash
+        );
+        frame.render_stateful_widget(
+            List::new(items)
+                .block(pane_block(&title, self.focus == Pane::Paths))
+                .highlight_style(Style::default().add_modifier(Modifier::REVERSED)),
+            right,
+            &mut self.path_list,
+        );
+
+        let status = if self.status.is_empty() {
+            Line::from("")
+        } else {
+            Line::from(Span::styled(
+                self.status.as_str(),
+                Style::default().fg(Color::Yellow),
+            ))
+        };
+        frame.render_widget(Paragraph::new(vec![status, Line::from(HELP)]), footer);
+
+        if self.confirming {
+            let area = centered(frame.area(), 60, 5);
+            frame.render_widget(Clear, area);
+            frame.render_widget(
+                Paragraph::new(format!(
+                    "Remove {} file(s), freeing {}? (y/n)",
+                    self.marked_count(),
+                    DuplicateStats::format_bytes(self.freed())
+                ))
+                .wrap(Wrap { trim: true })
+                .block(Block::default().borders(Borders::ALL).title("Apply")),
+                area,
+            );
+        }
+    }
+}
+
+fn pane_block(title: &str, focused: bool) -> Block<'_> {
+    let style = if focused {
+        Style::default().fg(Color::Yellow)
+    } else {
+        Style::default()
+    };
+    Block::default()
+        .borders(Borders::ALL)
+        .border_style(style)
+        .title(title)
+}
+
+/// A `width` by `height` area in the middle of `area`.
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+/// Runs the full-screen review until the user applies or quits.
+///
+/// `marked` holds the marks to start from, one per path of each group, and
+/// is updated with the marks the user leaves with, whether they apply them
+/// or not.
+///
+/// # Returns
+///
+/// A plan removing every marked file if the user applied their decisions,
+/// `None` if they quit, or an IO error from the terminal.
+pub fn review(
+    groups: &[DuplicateGroup],
+    policy: &KeepPolicy,
+    marked: &mut [Vec<bool>],
+) -> io::Result<Option<Plan>> {
+    if groups.is_empty() {
+        return Ok(None);
+    }
+
+    let mut review = Review::new(groups, policy).with_marks(marked);
+    let mut terminal = ratatui::try_init()?;
+    let outcome = run(&mut terminal, &mut review, policy);
+    ratatui::restore();
+
+    marked.clone_from_slice(&review.marked);
+    outcome
+}
+
+fn run(
+    terminal: &mut DefaultTerminal,
+    review: &mut Review,
+    policy: &KeepPolicy,
+) -> io::Result<Option<Plan>> {
+    loop {
+        terminal.draw(|frame| review.draw(frame))?;
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match review.handle(key, policy) {
+            Some(Outcome::Apply) => return Ok(Some(review.plan())),
+            Some(Outcome::Quit) => return Ok(None),
+            None => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::crossterm::event::KeyModifiers;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    fn group(size: u64, paths: &[&str]) -> DuplicateGroup {
+        DuplicateGroup {
+            hash: format!("hash{}", size),
+            size,
+            paths: paths.iter().map(PathBuf::from).collect(),
+            modified: HashMap::new(),
+        }
+    }
+
+    fn press(review: &mut Review, code: KeyCode) -> Option<Outcome> {
+        review.handle(
+            KeyEvent::new(code, KeyModifiers::NONE),
+            &KeepPolicy::default(),
+        )
+    }
+
+    #[test]
+    fn test_groups_sorted_by_wasted_space() {
+        let groups = vec![group(10, &["/a", "/b"]), group(100, &["/c", "/d", "/e"])];
+        let review = Review::new(&groups, &KeepPolicy::default());
+
+        assert_eq!(review.order, vec![1, 0]);
+        assert_eq!(review.group(), 1);
+    }
+
+    #[test]
+    fn test_last_copy_cannot_be_marked() {
+        let groups = vec![group(10, &["/a", "/b"])];
+        let mut review = Review::new(&groups, &KeepPolicy::default());
+
+        press(&mut review, KeyCode::Tab);
+        press(&mut review, KeyCode::Char(' '));
+        press(&mut review, KeyCode::Down);
+        press(&mut review, KeyCode::Char(' '));
+
+        assert_eq!(review.marked[0], vec![true, false]);
+        assert!(!review.status.is_empty());
+        assert_eq!(review.freed(), 10);
+    }
+
+    #[test]
+    fn test_auto_mark_and_apply() {
+        let groups = vec![group(10, &["/a", "/b"]), group(100, &["/c", "/d", "/e"])];
+        let mut review = Review::new(&groups, &KeepPolicy::default());
+
+        press(&mut review, KeyCode::Char('A'));
+        assert_eq!(review.marked_count(), 3);
+        assert_eq!(review.freed(), 210);
+
+        assert_eq!(press(&mut review, KeyCode::Char('x')), None);
+        assert!(review.confirming);
+        assert_eq!(press(&mut review, KeyCode::Char('y')), Some(Outcome::Apply));
+
+        let plan = review.plan();
+        assert_eq!(plan.total_bytes(), 210);
+        let victims: Vec<_> = plan.operations.iter().map(|op| op.victim.clone()).collect();
+        assert_eq!(
+            victims,
+            vec![
+                PathBuf::from("/d"),
+                PathBuf::from("/e"),
+                PathBuf::from("/b")
+            ]
+        );
+        assert!(plan
+            .operations
+            .iter()
+            .all(|op| op.keeper.is_some() && op.keeper.as_ref() != Some(&op.victim)));
+    }
+
+    #[test]
+    fn test_protected_files_stay_unmarked() {
+        let groups = vec![group(10, &["/keep/a", "/other/b", "/other/c"])];
+        let policy = KeepPolicy {
+            protected: vec![PathBuf::from("/keep")],
+            ..KeepPolicy::default()
+        };
+        let mut review = Review::new(&groups, &policy);
+
+        review.focus = Pane::Paths;
+        review.path_list.select(Some(1));
+        review.keep_only();
+
+        assert_eq!(review.marked[0], vec![false, false, true]);
+    }
+
+    #[test]
+    fn test_starts_from_earlier_marks() {
+        let groups = vec![group(10, &["/keep/a", "/b", "/c"]), group(5, &["/d", "/e"])];
+        let policy = KeepPolicy {
+            protected: vec![PathBuf::from("/keep")],
+            ..KeepPolicy::default()
+        };
+
+        let review =
+            Review::new(&groups, &policy).with_marks(&[vec![true, true, false], vec![true, true]]);
+
+        assert_eq!(
+            review.marked,
+            vec![vec![false, true, false], vec![false, false]]
+        );
+    }
+}